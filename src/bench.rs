@@ -0,0 +1,227 @@
+//! The benchmarks of `amargo bench`, every source of `benches/` declares
+//! benchmarks with the `BENCH(name)` macro of the bundled `amargo_bench.h`
+//! (or is a program timed as a whole), the results are stored on
+//! `target/bench` to compare every run with the previous one
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    process::{self, Stdio},
+    time::Instant,
+};
+
+use crate::{
+    error::*,
+    test::{Harness, TestProgram},
+};
+
+use console::style;
+
+/// The harness of the benchmarks, the first argument selects the benchmark
+/// and the second one the number of samples
+pub(crate) const BENCH_HARNESS: Harness = Harness {
+    header: "amargo_bench.h",
+    data: include_str!("../templates/amargo_bench.h"),
+    macro_name: "BENCH",
+    main: "#include <stdlib.h>\n\
+           \n\
+           int main(int argc, char **argv) {\n\
+           \x20   size_t i;\n\
+           \x20   int samples = argc > 2 ? atoi(argv[2]) : 30;\n\
+           \x20   for (i = 0; i < sizeof(amargo_cases) / sizeof(amargo_cases[0]); i++) {\n\
+           \x20       if (argc > 1 && strcmp(argv[1], amargo_cases[i].name) == 0) {\n\
+           \x20           amargo_bench_run(amargo_cases[i].run, samples);\n\
+           \x20           return 0;\n\
+           \x20       }\n\
+           \x20   }\n\
+           \x20   return 2;\n\
+           }\n",
+};
+
+/// Name of the file with the results of the last run inside `target/bench`
+const BASELINE_FILE: &str = "baseline.json";
+
+/// The statistics of a benchmark, the times are nanoseconds per iteration
+#[derive(Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub(crate) struct BenchStats {
+    /// Iterations of every sample
+    pub iterations: u64,
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+}
+
+impl BenchStats {
+    /// Compute the statistics of the `samples`
+    fn new(iterations: u64, mut samples: Vec<f64>) -> Self {
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let n = samples.len().max(1) as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let median = match samples.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => {
+                (samples[len / 2 - 1] + samples[len / 2]) / 2.0
+            },
+            len => samples[len / 2],
+        };
+        let variance =
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+
+        BenchStats {
+            iterations,
+            mean,
+            median,
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+/// Format `ns` nanoseconds with the most readable unit
+fn format_time(ns: f64) -> String {
+    match ns {
+        ns if ns >= 1e9 => format!("{:.2} s", ns / 1e9),
+        ns if ns >= 1e6 => format!("{:.2} ms", ns / 1e6),
+        ns if ns >= 1e3 => format!("{:.2} µs", ns / 1e3),
+        ns => format!("{:.2} ns", ns),
+    }
+}
+
+/// Run the benchmark `case` of `program` (or the whole `program` if there
+/// is no case) taking `samples` samples
+fn run_bench(
+    program: &TestProgram,
+    case: Option<&str>,
+    samples: usize,
+) -> Result<std::result::Result<BenchStats, String>> {
+    // Programs without cases are timed as a whole after a warmup run
+    let case = match case {
+        Some(case) => case,
+        None => {
+            let mut times = Vec::new();
+            for i in 0..=samples {
+                let start = Instant::now();
+                let status = process::Command::new(&program.path)
+                    .stdout(Stdio::null())
+                    .status()
+                    .map_err(|e| {
+                        Error::ProcessCreation(program.path.clone(), e)
+                    })?;
+                if !status.success() {
+                    return Ok(Err(format!("{}", status)));
+                }
+                if i > 0 {
+                    times.push(start.elapsed().as_secs_f64() * 1e9);
+                }
+            }
+
+            return Ok(Ok(BenchStats::new(1, times)));
+        },
+    };
+
+    let output = process::Command::new(&program.path)
+        .arg(case)
+        .arg(samples.to_string())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| Error::ProcessCreation(program.path.clone(), e))?;
+    if !output.status.success() {
+        return Ok(Err(format!("{}", output.status)));
+    }
+
+    // The first line has the iterations of every sample and then there is
+    // a line per sample with the nanoseconds per iteration
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let iterations = lines
+        .next()
+        .and_then(|l| l.strip_prefix("iterations "))
+        .and_then(|i| i.trim().parse().ok())
+        .unwrap_or(1);
+    let times = lines
+        .filter_map(|l| l.trim().parse().ok())
+        .collect::<Vec<f64>>();
+
+    Ok(Ok(BenchStats::new(iterations, times)))
+}
+
+/// Run the benchmarks of the `programs` that match the `filter` one after
+/// another, printing their statistics and the change against the last run
+/// stored on `dir`, returns the number of failed benchmarks
+pub(crate) fn run(
+    programs: &[TestProgram],
+    filter: Option<&str>,
+    samples: usize,
+    dir: &Path,
+) -> Result<usize> {
+    let baseline_path = dir.join(BASELINE_FILE);
+    let mut baseline: BTreeMap<String, BenchStats> = fs::read(&baseline_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+
+    let benches = programs
+        .iter()
+        .flat_map(|program| {
+            program
+                .named_cases()
+                .into_iter()
+                .map(move |(case, name)| (program, case, name))
+        })
+        .filter(|(_, _, name)| filter.is_none_or(|f| name.contains(f)))
+        .collect::<Vec<_>>();
+
+    println!("\nrunning {} benches", benches.len());
+
+    let mut failed = 0;
+    for (program, case, name) in benches {
+        let stats = match run_bench(program, case, samples.max(1))? {
+            Ok(stats) => stats,
+            Err(reason) => {
+                println!(
+                    "bench {} ... {} ({})",
+                    name,
+                    style("FAILED").red(),
+                    reason
+                );
+                failed += 1;
+                continue;
+            },
+        };
+
+        let change = match baseline.get(&name) {
+            Some(last) if last.mean > 0.0 => {
+                let change = (stats.mean - last.mean) / last.mean * 100.0;
+                let text = format!("{:+.2}%", change);
+                match change {
+                    c if c <= -2.0 => style(text).green().to_string(),
+                    c if c >= 2.0 => style(text).red().to_string(),
+                    _ => text,
+                }
+            },
+            _ => String::from("no baseline"),
+        };
+        println!(
+            "bench {} ... {}/iter (median {}, ± {}) [{}]",
+            name,
+            style(format_time(stats.mean)).bold(),
+            format_time(stats.median),
+            format_time(stats.stddev),
+            change
+        );
+        baseline.insert(name, stats);
+    }
+
+    // The results of this run are the baseline of the next one
+    fs::create_dir_all(dir)
+        .map_err(|e| Error::CannotCreate(dir.to_path_buf(), e))?;
+    fs::write(
+        &baseline_path,
+        serde_json::to_string_pretty(&baseline).unwrap(),
+    )
+    .map_err(|e| Error::CannotCreate(baseline_path, e))?;
+
+    println!();
+
+    Ok(failed)
+}
@@ -4,18 +4,37 @@ use std::{
     collections::VecDeque,
     fs,
     path::{Path, PathBuf},
+    process::{self, Stdio},
     time::SystemTime,
 };
 
 use crate::{
-    config::{BuildType, ProjectConfig},
+    bench::BENCH_HARNESS,
+    compdb::{self, CompileCommand},
+    config::{
+        Config, Language, MessageFormat, Profile, ProjectConfig, ProjectType,
+    },
+    depfile,
+    diagnostic::{self, Diagnostic, Severity},
     error::*,
-    tool::Tool,
+    message::Message,
+    pool,
+    state::{self, BuildState, LinkState, ObjectState},
+    test::{Harness, TestProgram, TEST_HARNESS},
+    tool::{Archiver, Tool, ToolFamily},
     EXE_EXTENSION,
 };
 
+use console::style;
 use log::info;
 
+/// The last modification time of `path`
+fn modified(path: &Path) -> Result<SystemTime> {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .map_err(|e| Error::CannotRead(path.to_path_buf(), e))
+}
+
 /// Any type that can be extracted from a directory in group
 trait FromDir: From<(PathBuf, SystemTime)> {
     const EXTS: &'static [&'static str];
@@ -36,14 +55,14 @@ trait FromDir: From<(PathBuf, SystemTime)> {
             // DirEntry -> PathBuf
             let path = entry.map_err(Error::FileListing)?.into_path();
 
-            if !path.is_file() || path.extension().is_none() {
+            if !path.is_file() {
                 continue;
             }
 
             // Push the found object files and the last build time
-            let extension = path.extension().unwrap();
-            if Self::EXTS.contains(&extension.to_str().unwrap()) {
-                let modif = path.metadata().unwrap().modified().unwrap();
+            let extension = path.extension().and_then(|e| e.to_str());
+            if extension.is_some_and(|e| Self::EXTS.contains(&e)) {
+                let modif = modified(&path)?;
                 result.push(Self::from((path, modif)));
             }
         }
@@ -70,13 +89,23 @@ macro_rules! impl_from_dir {
     };
 }
 
-/// A source file *.c, *.cpp or *.cxx
+/// A source file *.c, *.cpp, *.cxx or *.cc
 #[derive(Debug, Clone)]
 pub(crate) struct Source {
     path: PathBuf,
     modif: SystemTime,
 }
-impl_from_dir!(Source, &["c", "cpp", "cxx"]);
+impl_from_dir!(Source, &["c", "cpp", "cxx", "cc"]);
+
+impl Source {
+    /// The language of the source given its extension
+    pub fn language(&self) -> Language {
+        match self.path.extension().and_then(|e| e.to_str()) {
+            Some("c") => Language::C,
+            _ => Language::Cpp,
+        }
+    }
+}
 
 /// A header file *.h, *.hpp or *.hxx
 #[derive(Debug, Clone)]
@@ -94,56 +123,225 @@ pub(crate) struct Object {
 }
 impl_from_dir!(Object, &["o", "obj"]);
 
-// Returns the direct dependencies `Vec<Header>` of a `Header` or a `Source`
-// given the actual `path` of the file to extract dependencies, the expected
-// extensions to find and the list of possible dependencies
-//
-// TODO: allow detecting "#include "something.c"
-macro_rules! direct_dependencies {
-    ($path:expr, $dep_exts:expr, $deps:expr) => {{
-        let mut deps = Vec::new();
-
-        // Extract the source data
-        let source_data = fs::read_to_string(&$path)
-            .map_err(|e| Error::CannotRead($path.clone(), e))?;
-
-        // Find in the source all the #include "<header>"
-        // FIXME: Maybe bug in regex but the '^' and '$' doesn't seem to work
-        // very well (and they are mandatory unless I use a full custom c
-        // parser)
-        let re = &format!(
-            r#"#include\s*"(?P<dep_name>\w*\.({}))""#,
-            $dep_exts.join("|")
-        );
-        let re = regex::Regex::new(re).unwrap();
-        let caps = re.captures_iter(&source_data[..]);
-        let mut dep_names = caps
-            .map(|cap| cap["dep_name"].to_string())
-            .collect::<Vec<String>>();
+/// Find the `#include "<name>"` of a line of a source or a header
+fn quoted_include(line: &str) -> Option<&str> {
+    thread_local! {
+        static INCLUDE_RE: regex::Regex =
+            regex::Regex::new(r#"^\s*#\s*include\s*"(?P<name>[^"]*)""#)
+                .unwrap();
+    }
 
-        // Iterate over all the possible dependencies
-        for (i, dep) in $deps.iter().enumerate() {
-            // If an matching `dep.path.filename` is found in the headers listed
-            // in the C source, remove it from the vec, as later on
-            // if the len of `headers` != 0 will mean that there
-            // were unresolved imports
-            dep_names.retain(|dep_name| {
-                if dep_name.as_str() == dep.path.file_name().unwrap() {
-                    deps.push(i);
-                    false
-                } else {
-                    true
+    INCLUDE_RE.with(|re| {
+        re.captures(line)
+            .and_then(|cap| cap.name("name"))
+            .map(|name| &line[name.range()])
+    })
+}
+
+/// Run the link `command` of `target` (with the tool at `program`), the
+/// output of the linker is passed through to stderr
+fn run_link(
+    mut command: process::Command,
+    program: PathBuf,
+    target: &Path,
+) -> Result<()> {
+    let output = command
+        .output()
+        .map_err(|e| Error::ProcessCreation(program, e))?;
+    eprint!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        let command_line = state::command_line(&command);
+        return Err(Error::CannotLink(target.to_path_buf(), command_line));
+    }
+
+    Ok(())
+}
+
+/// Check if `source` needs to be compiled again into `object` with
+/// `command` by the compiler `compiler`, given the `state` of the object on
+/// the last build
+fn needs_compile(
+    source: &Source,
+    object: &Path,
+    command: &[String],
+    compiler: &str,
+    state: Option<&ObjectState>,
+) -> bool {
+    let object_modif = match object.metadata().and_then(|m| m.modified()) {
+        Ok(modif) => modif,
+        Err(_) => return true,
+    };
+    if source.modif > object_modif {
+        return true;
+    }
+
+    // Without the state there is no way to know if it's up to date
+    let state = match state {
+        Some(state) => state,
+        None => return true,
+    };
+    if state.source != source.path
+        || state.command != command
+        || state.compiler != compiler
+    {
+        info!("Command or compiler of {:?} changed", source.path);
+        return true;
+    }
+
+    // Compile again if any dependency has been modified or removed
+    let changed = state.deps.iter().any(|dep| {
+        dep.metadata()
+            .and_then(|m| m.modified())
+            .map_or(true, |modif| modif > object_modif)
+    });
+    if changed {
+        info!("Dependencies of {:?} changed", source.path);
+    }
+
+    changed
+}
+
+/// Check if `source` defines a `main` function, those sources are left out
+/// of the tests and the examples
+fn defines_main(source: &Path) -> Result<bool> {
+    let data = fs::read_to_string(source)
+        .map_err(|e| Error::CannotRead(source.to_path_buf(), e))?;
+    let re = regex::Regex::new(r"(?m)^\s*(?:int|void)\s+main\s*\(").unwrap();
+
+    Ok(re.is_match(&data))
+}
+
+/// The compilation of a single source into an object
+struct CompileJob {
+    source: Source,
+    object: PathBuf,
+    depfile: PathBuf,
+    command: process::Command,
+    command_line: Vec<String>,
+    tool: Tool,
+}
+
+impl CompileJob {
+    /// Run the compiler and return how the object has been generated, the
+    /// diagnostics of the compiler are rendered with the paths relative to
+    /// `base` or emitted as messages depending on `format`
+    fn run(
+        mut self,
+        base: &Path,
+        format: MessageFormat,
+    ) -> Result<ObjectState> {
+        info!("Compiling {:?}", self.source);
+        if format == MessageFormat::Json {
+            Message::CompilerStarted {
+                source: &self.source.path,
+                object: &self.object,
+            }
+            .emit();
+        }
+
+        // Capture the output to render the diagnostics (MSVC prints them and
+        // the dependencies to stdout)
+        let output = self
+            .command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|child| child.wait_with_output())
+            .map_err(|e| Error::ProcessCreation(self.tool.path.clone(), e))?;
+        let mut text = String::from_utf8_lossy(&output.stderr).into_owned();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        if let ToolFamily::Msvc { .. } = self.tool.family {
+            let (mut deps, rest) = depfile::parse_show_includes(&stdout);
+            text.push_str(&rest);
+            deps.insert(0, self.source.path.clone());
+            depfile::write(&self.depfile, &self.object, &deps)?;
+        } else {
+            text.push_str(&stdout);
+        }
+
+        // Render the diagnostics all at once so the ones of different
+        // sources don't get mixed, if the output couldn't be understood show
+        // it as it is
+        let mut diagnostics = diagnostic::parse(&text);
+        if format == MessageFormat::Json {
+            // The output that couldn't be understood is reported by the
+            // compiler without location
+            if diagnostics.is_empty()
+                && !output.status.success()
+                && !text.trim().is_empty()
+            {
+                diagnostics.push(Diagnostic {
+                    file: self.tool.path.clone(),
+                    line: None,
+                    column: None,
+                    severity: Severity::Error,
+                    message: text.trim().to_string(),
+                    notes: Vec::new(),
+                });
+            }
+            for diagnostic in &diagnostics {
+                Message::CompilerMessage {
+                    source: &self.source.path,
+                    diagnostic,
                 }
-            });
+                .emit();
+            }
+            Message::CompilerFinished {
+                source: &self.source.path,
+                object: &self.object,
+                success: output.status.success(),
+            }
+            .emit();
+        } else if !diagnostics.is_empty() {
+            let rendered = diagnostics
+                .iter()
+                .map(|d| d.render(base))
+                .collect::<Vec<String>>()
+                .join("\n");
+            eprintln!("{}", rendered);
+        } else if !output.status.success() && !text.trim().is_empty() {
+            eprint!("{}", text);
         }
 
-        // Check if all the includes were resolved
-        if dep_names.len() != 0 {
-            return Err(Error::MissingIncludes($path.clone(), dep_names));
+        if !output.status.success() {
+            return Err(Error::Compilation(vec![self.source.path]));
         }
 
-        deps
-    }};
+        Ok(ObjectState {
+            deps: depfile::read(&self.depfile)
+                .unwrap_or_else(|| vec![self.source.path.clone()]),
+            source: self.source.path,
+            command: self.command_line,
+            compiler: self.tool.identity,
+        })
+    }
+}
+
+/// Which examples to build
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Examples<'a> {
+    All,
+
+    /// Only the example with this name
+    Only(&'a str),
+}
+
+/// What the projects that depend on a library need from it once built
+#[derive(Clone)]
+struct BuiltDependency {
+    /// The root of the library project
+    root: PathBuf,
+
+    /// Its headers and the ones of its dependencies
+    header_dirs: Vec<PathBuf>,
+
+    /// Its library and the ones of its dependencies, in link order
+    libraries: Vec<PathBuf>,
+
+    /// The directories of the dynamic libraries among `libraries`
+    library_dirs: Vec<PathBuf>,
 }
 
 /// This let us build given a config a project
@@ -153,95 +351,148 @@ pub struct Build<'a> {
     /// config file
     config: &'a ProjectConfig,
 
+    /// The `Amargo.toml` of the project
+    manifest: &'a Config,
+
     /// Locations where to find the headers, needed by the compiler
     header_dirs: Vec<PathBuf>,
 
     /// The objects needed at linkage
     objects: Vec<Object>,
 
-    /// The sources need to compile, not necesarely all the project sources
-    /// just the ones that need to recompile
+    /// All the sources of the project, not all of them need to be compiled
     sources: Vec<Source>,
 
     /// The headers found in the include locations
     headers: Vec<Header>,
 
-    /// A graph represented as an adjacency matrix where its rows/columns are
-    /// indexed by the indices of the virtual vector `sources` + `headers`
+    /// A graph represented as an adjacency list where its rows/columns are
+    /// indexed by the indices of `headers`, only needed to group the headers
+    /// of header-only projects
     dependency_graph: Vec<Vec<usize>>,
 
     /// Contains the last build time of the last target (if exists)
     last_time: Option<SystemTime>,
 
-    /// The tool used for compilation, abstraction over the compiler, this
-    /// in the future must encompass linker, assembler and even external tools
-    /// to reduce binary/library size
-    tool: Tool,
+    /// The settings of the selected profile
+    profile: Profile,
+
+    /// The tools used for compilation, abstraction over the compiler, one
+    /// per language found in the sources, this in the future must encompass
+    /// linker, assembler and even external tools to reduce binary/library
+    /// size
+    tools: Vec<Tool>,
 
-    /// The directory where to put the target (influenced by the `mode`)
+    /// The language of the driver used to link, C++ if there is any C++
+    /// source so the C++ runtime is linked
+    link_language: Language,
+
+    /// The directory where to put the target (influenced by the profile)
     out_dir: PathBuf,
+
+    /// The state of the last build on `out_dir`
+    state: BuildState,
+
+    /// Maximum number of compilers running at the same time
+    jobs: usize,
+
+    /// Keep compiling the rest of sources when one of them fails
+    keep_going: bool,
+
+    /// Generate position independent code even if the project is not a
+    /// dynamic library, the dependencies can be linked into one
+    pic: bool,
+
+    /// The names of the projects that depend on this one (when it's built
+    /// as a dependency), to detect dependency cycles
+    dependents: Vec<String>,
+
+    /// The dependencies already built, every one is built once even if
+    /// several projects depend on it
+    built: Vec<BuiltDependency>,
+
+    /// The libraries of the dependencies (and theirs) in the order they
+    /// must be linked
+    libraries: Vec<PathBuf>,
+
+    /// The directories of the dynamic libraries of the dependencies, where
+    /// the linked programs look for them at runtime
+    library_dirs: Vec<PathBuf>,
 }
 
 impl<'a> Build<'a> {
     /// Construct a new instance of a blank set of configurations
     pub fn new(
         config: &'a ProjectConfig,
-        mode: BuildType,
+        profile_name: &str,
     ) -> Result<Build<'a>> {
-        let project_name = &config.config.as_ref().unwrap().project.name;
+        let profile = config.profile(profile_name)?;
+        let out_dir = config.working_dir.join("target").join(profile_name);
+        Self::with_profile(config, out_dir, profile, false)
+    }
 
-        // Push compiler args depending on the `mode`
-        let mut tool = Tool::default();
-        tool.push_cc_arg(tool.family.warnings_flags().into());
-        if mode == BuildType::Debug {
-            tool.push_cc_arg(tool.family.debug_flags().into());
-        } else {
-            tool.push_cc_arg(tool.family.release_flags().into());
-        }
+    /// Like `new` but with the settings of the profile already resolved and
+    /// the directory of the artifacts, the dependencies are built with the
+    /// profile of the project that depends on them inside its `out_dir`
+    /// (and with position independent code if `pic`)
+    fn with_profile(
+        config: &'a ProjectConfig,
+        out_dir: PathBuf,
+        profile: Profile,
+        pic: bool,
+    ) -> Result<Build<'a>> {
+        let manifest = config.manifest()?;
+        let language = manifest.project.language;
 
         // Create the "default" `Build` struct
         //
         // TODO: check if ..Default::default() works
         let mut build = Build {
             config,
+            manifest,
             header_dirs: Vec::new(),
             objects: Vec::new(),
             sources: Vec::new(),
             headers: Vec::new(),
             dependency_graph: Vec::new(),
             last_time: None,
-            tool,
-            out_dir: mode.into(),
+            profile,
+            tools: Vec::new(),
+            link_language: language,
+            out_dir,
+            state: BuildState::default(),
+            jobs: 1,
+            keep_going: false,
+            pic,
+            dependents: Vec::new(),
+            built: Vec::new(),
+            libraries: Vec::new(),
+            library_dirs: Vec::new(),
         };
 
-        info!("Selected build tool: {:?}", &build.tool);
+        // The tool of the project language is always needed, at least to
+        // know the name of the target
+        build.load_tool(language)?;
 
         // Create the build target dir if it does not exist
         fs::create_dir_all(&build.out_dir)
             .map_err(|e| Error::CannotCreate(build.out_dir.clone(), e))?;
 
-        // Look for existing object files in the `target/<mode>` dir and add
-        // them to the `Build`
-        build.objects = Object::from_dir(&build.out_dir)?;
+        build.state = BuildState::load(&build.out_dir);
 
         // Get last build time retrieving looking at the path of the last build
-        // target at `target/<mode>/<project_name>.EXE_EXTENSION`, if the last
-        // build time is less than any of the objects delete the target
-        let target_path = build
-            .out_dir
-            .join(project_name)
-            .with_extension(EXE_EXTENSION);
+        // target at `target/<profile>/<target_name>`, if the last build time
+        // is less than any of the objects delete the target
+        let target_path = build.target_path();
         if target_path.exists() {
-            build.last_time = target_path.metadata().unwrap().modified().ok();
+            build.last_time = modified(&target_path).ok();
             info!(
                 "Found target {:?} with last build time {:?} ago",
                 target_path,
-                build.last_time.unwrap().elapsed().unwrap()
+                build.last_time.and_then(|t| t.elapsed().ok())
             );
         }
 
-        info!("Found objects at {:?}: {:#?}", build.out_dir, build.objects);
-
         Ok(build)
     }
 
@@ -254,11 +505,79 @@ impl<'a> Build<'a> {
         let dir = self.config.working_dir.join(files_dir);
         self.sources.extend(Source::from_dir(dir)?);
 
+        // Link with the C++ driver if there is any C++ source
+        if self.sources.iter().any(|s| s.language() == Language::Cpp) {
+            self.link_language = Language::Cpp;
+        }
+
         info!("Added sources: {:#?}", &self.sources);
 
         Ok(self)
     }
 
+    /// Get the tool that compiles `language`, creating and configuring it the
+    /// first time it's needed
+    fn load_tool(&mut self, language: Language) -> Result<&Tool> {
+        if !self.tools.iter().any(|t| t.language == language) {
+            let tool = self.configure_tool(language)?;
+            info!("Selected build tool: {:?}", &tool);
+            self.tools.push(tool);
+        }
+
+        Ok(self.tool(language))
+    }
+
+    /// Get the tool that compiles `language`, it must be already loaded
+    fn tool(&self, language: Language) -> &Tool {
+        self.tools.iter().find(|t| t.language == language).unwrap()
+    }
+
+    /// Create the tool for `language` and push the compiler and linker args
+    /// depending on the project and the profile
+    fn configure_tool(&self, language: Language) -> Result<Tool> {
+        let project = &self.manifest.project;
+        let profile = &self.profile;
+
+        let mut tool = Tool::new(language)?;
+        tool.push_cc_arg(tool.family.warnings_flags().into());
+        if profile.warnings_as_errors.unwrap() {
+            tool.push_cc_arg(tool.family.warnings_to_errors_flag().into());
+        }
+        tool.push_cc_arg(
+            tool.family
+                .opt_level_flag(profile.opt_level.unwrap())
+                .into(),
+        );
+        if profile.debug.unwrap() {
+            tool.push_cc_arg(tool.family.debug_flags().into());
+        }
+        for define in &profile.defines {
+            tool.push_cc_arg(
+                format!("{}{}", tool.family.define_flag(), define).into(),
+            );
+        }
+        let (standard, flags) = match language {
+            Language::C => (&project.c_standard, &profile.cflags),
+            Language::Cpp => (&project.cpp_standard, &profile.cxxflags),
+        };
+        if let Some(standard) = standard {
+            tool.push_standard(standard)?;
+        }
+
+        // Dynamic libraries need position independent code
+        if project.kind == ProjectType::DynamicLib || self.pic {
+            tool.push_cc_arg_opt(tool.family.pic_flag());
+        }
+        for flag in flags {
+            tool.push_cc_arg(flag.into());
+        }
+        for flag in &profile.ldflags {
+            tool.push_ld_arg(flag.into());
+        }
+
+        Ok(tool)
+    }
+
     /// Add include dir
     #[inline]
     pub fn include<P: AsRef<Path>>(
@@ -274,178 +593,1010 @@ impl<'a> Build<'a> {
         Ok(self)
     }
 
+    /// Build the `[dependencies]` of the project (and theirs) with the same
+    /// profile, their headers are added to the include locations and their
+    /// libraries are linked after the objects of the project
+    ///
+    /// The dependencies go to `target/<profile>/deps/<name>` of the project
+    /// being built, always with position independent code as they can end
+    /// up linked into a dynamic library
+    pub fn dependencies(&mut self) -> Result<&mut Build<'a>> {
+        let manifest = self.manifest;
+        let mut dependents = self.dependents.clone();
+        dependents.push(manifest.project.name.clone());
+
+        for (name, dependency) in &manifest.dependencies {
+            if dependents.contains(name) {
+                return Err(Error::InvalidDependency(
+                    name.clone(),
+                    format!(
+                        "cyclic dependency {} -> {}",
+                        dependents.join(" -> "),
+                        name
+                    ),
+                ));
+            }
+
+            let config = self.config.dependency(name, dependency)?;
+            let built = match self
+                .built
+                .iter()
+                .find(|b| b.root == config.working_dir)
+            {
+                Some(built) => built.clone(),
+                None => {
+                    let built = self.build_dependency(&config, &dependents)?;
+                    self.built.push(built.clone());
+                    built
+                },
+            };
+
+            for dir in built.header_dirs {
+                if !self.header_dirs.contains(&dir) {
+                    self.header_dirs.push(dir);
+                }
+            }
+
+            // The libraries go before the ones they depend on, so if some of
+            // them is already linked it's moved after this one
+            for library in built.libraries {
+                self.libraries.retain(|l| l != &library);
+                self.libraries.push(library);
+            }
+            for dir in built.library_dirs {
+                if !self.library_dirs.contains(&dir) {
+                    self.library_dirs.push(dir);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Build the dependency of `config` (and its dependencies), the
+    /// `dependents` are the projects that lead to it
+    fn build_dependency(
+        &mut self,
+        config: &ProjectConfig,
+        dependents: &[String],
+    ) -> Result<BuiltDependency> {
+        let project = &config.manifest()?.project;
+        if config.cli.message_format == MessageFormat::Human {
+            println!("{:>12} {:?}", style("Compiling").cyan(), project.name);
+        }
+
+        // All the dependencies go to the `deps` of the project being built
+        let deps_dir = match self.dependents.is_empty() {
+            true => self.out_dir.join("deps"),
+            false => self.out_dir.parent().unwrap().to_path_buf(),
+        };
+        let mut build = Build::with_profile(
+            config,
+            deps_dir.join(&project.name),
+            self.profile.clone(),
+            true,
+        )?;
+        build.dependents = dependents.to_vec();
+        build.built = std::mem::take(&mut self.built);
+        build
+            .jobs(self.jobs)
+            .keep_going(self.keep_going)
+            .include("include")?
+            .dependencies()?;
+        if project.kind == ProjectType::HeaderOnly {
+            build.amalgamate()?;
+        } else {
+            build.files("src")?.compile()?.link()?;
+        }
+        self.built = std::mem::take(&mut build.built);
+
+        let mut libraries: Vec<_> = build.link_input().into_iter().collect();
+        libraries.append(&mut build.libraries);
+        if project.kind == ProjectType::DynamicLib {
+            build.library_dirs.insert(0, build.out_dir.clone());
+        }
+
+        Ok(BuiltDependency {
+            root: config.working_dir.clone(),
+            header_dirs: build.header_dirs,
+            libraries,
+            library_dirs: build.library_dirs,
+        })
+    }
+
+    /// The library that the projects that depend on this one link with (if
+    /// any), the import library of the dll on Windows
+    fn link_input(&self) -> Option<PathBuf> {
+        let project = &self.manifest.project;
+        let family = self.tool(project.language).family;
+
+        match project.kind {
+            ProjectType::StaticLib => Some(self.target_path()),
+            ProjectType::DynamicLib => {
+                let import_lib = family.import_lib_name(&project.name);
+                Some(import_lib.map_or_else(
+                    || self.target_path(),
+                    |name| self.out_dir.join(name),
+                ))
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the tool that links `language`, with the flags to find the dynamic
+    /// libraries of the dependencies at runtime
+    fn link_tool(&mut self, language: Language) -> Result<Tool> {
+        let mut tool = self.load_tool(language)?.clone();
+        for dir in &self.library_dirs {
+            if let Some(flag) = tool.family.rpath_flag(dir) {
+                tool.push_ld_arg(flag);
+            }
+        }
+
+        Ok(tool)
+    }
+
+    /// The libraries of the dependencies as objects to link
+    fn dependency_libraries(&self) -> Result<Vec<Object>> {
+        self.libraries
+            .iter()
+            .map(|path| {
+                modified(path).map(|modif| Object::from((path.clone(), modif)))
+            })
+            .collect()
+    }
+
+    /// Resolve the `#include "<name>"` of `from` like the compiler does,
+    /// first relative to the directory of `from` and then on the include
+    /// locations
+    fn resolve_include(&self, from: &Path, name: &str) -> Option<PathBuf> {
+        from.parent()
+            .into_iter()
+            .chain(self.header_dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .map(|path| path.canonicalize().unwrap_or(path))
+    }
+
+    /// The index on `headers` of the header of the project included with
+    /// `#include "<name>"` from `from`, `None` if it's not one of them
+    fn project_header(&self, from: &Path, name: &str) -> Option<usize> {
+        let path = self.resolve_include(from, name)?;
+        self.headers.iter().position(|h| {
+            h.path == path || h.path.canonicalize().is_ok_and(|h| h == path)
+        })
+    }
+
+    /// Fill `dependency_graph` with the direct dependencies of every
+    /// `Header` (the `#include "<header>"` relationships between the headers
+    /// of the project), the includes that resolve outside the project (like
+    /// the headers of the dependencies) are not part of the graph
+    fn build_dependency_graph(&mut self) -> Result<()> {
+        let mut graph = Vec::with_capacity(self.headers.len());
+        for header in &self.headers {
+            let data = fs::read_to_string(&header.path)
+                .map_err(|e| Error::CannotRead(header.path.clone(), e))?;
+
+            let mut deps = Vec::new();
+            let mut missing = Vec::new();
+            for name in data.lines().filter_map(quoted_include) {
+                match self.project_header(&header.path, name) {
+                    Some(dep) => deps.push(dep),
+                    None => {
+                        if self.resolve_include(&header.path, name).is_none() {
+                            missing.push(name.to_string());
+                        }
+                    },
+                }
+            }
+            if !missing.is_empty() {
+                let path = header.path.clone();
+                return Err(Error::MissingIncludes(path, missing));
+            }
+
+            graph.push(deps);
+        }
+        self.dependency_graph = graph;
+
+        info!("Dependency graph: {:?}", self.dependency_graph);
+
+        Ok(())
+    }
+
     /// Compile the sources to objects (if they need to)
+    ///
+    /// A source is compiled again if its object doesn't exist, if the
+    /// command line or the compiler changed since the last build or if the
+    /// source or any of the dependencies discovered by the compiler on the
+    /// last compilation is newer than the object
+    ///
+    /// The commands of all the sources are recorded on the
+    /// `compile_commands.json` at the root of the project
     pub fn compile(&mut self) -> Result<&mut Build<'a>> {
-        // Just do the incremental compilation if this is not the first build
-        //
-        // Representing the dependencies as a graph and updating the source
-        // `.modif` to the bigger `.modif` of him within his
-        // dependencies, then sorting the sources thet need compilation
-        if let Some(last_time) = self.last_time {
-            // Initialize the dependency_graph full of 0s (falses)
-            let size = self.sources.len() + self.headers.len();
-            self.dependency_graph = vec![vec![]; size];
-
-            // Fill the adjacency matrix of the graph of dependencies with
-            // `Source`s and `Include`s
-            for (src_idx, source) in self.sources.iter().enumerate() {
-                let mut dep_indices = direct_dependencies!(
-                    source.path,
-                    &["h", "hpp", "hxx"],
-                    self.headers
-                );
-                // FIXME: Dirty fix until #include "name.c" is supported
-                dep_indices
-                    .iter_mut()
-                    .for_each(|i| *i += self.sources.len());
+        self.remove_stale_objects()?;
+
+        // Generate the command of every source, the already configured tool
+        // will take care of providing a correct command
+        let mut jobs = Vec::new();
+        for source in self.sources.clone() {
+            let object = self.object_path(&source);
+            let header_dirs = self.header_dirs.clone();
+            jobs.push(self.compile_job(source, object, &header_dirs)?);
+        }
 
-                self.dependency_graph[src_idx] = dep_indices;
-            }
-            for (src_idx, header) in self.headers.iter().enumerate() {
-                let src_idx = src_idx + self.sources.len();
-                let mut dep_indices = direct_dependencies!(
-                    header.path,
-                    &["h", "hpp", "hxx"],
-                    self.headers
-                );
+        let commands = jobs.iter().map(|job| self.compile_command(job));
+        self.record_commands(commands.collect())?;
+
+        self.run_jobs(jobs)?;
+
+        Ok(self)
+    }
 
-                // FIXME: Dirty fix until #include "name.c" is supported
-                dep_indices
-                    .iter_mut()
-                    .for_each(|i| *i += self.sources.len());
+    /// How `job` compiles its source, for the compilation database
+    fn compile_command(&self, job: &CompileJob) -> CompileCommand {
+        CompileCommand {
+            directory: self.config.working_dir.clone(),
+            file: job.source.path.clone(),
+            arguments: job.command_line.clone(),
+            output: job.object.clone(),
+        }
+    }
 
-                self.dependency_graph[src_idx] = dep_indices;
+    /// Record the `commands` on the `compile_commands.json` at the root of
+    /// the project, unless it's built as a dependency of another one
+    fn record_commands(&self, commands: Vec<CompileCommand>) -> Result<()> {
+        match self.dependents.is_empty() {
+            true => compdb::update(&self.config.working_dir, commands),
+            false => Ok(()),
+        }
+    }
+
+    /// Generate the job that compiles `source` into `object` looking for
+    /// headers on `include_dirs`
+    fn compile_job(
+        &mut self,
+        source: Source,
+        object: PathBuf,
+        include_dirs: &[PathBuf],
+    ) -> Result<CompileJob> {
+        let tool = self.load_tool(source.language())?.clone();
+
+        // FIXME: Maybe no need to specify "-o <source_name>.o" to the
+        // compiler
+        let depfile = object.with_extension("d");
+        let mut command = tool.to_build_command(include_dirs, &depfile);
+        command.arg(&object).arg(&source.path);
+
+        Ok(CompileJob {
+            source,
+            object,
+            depfile,
+            command_line: state::command_line(&command),
+            command,
+            tool,
+        })
+    }
+
+    /// Run the `jobs` that need to be compiled, keeping `self.jobs` compilers
+    /// running, on the first failure no more compilations are started unless
+    /// `self.keep_going`
+    fn run_jobs(&mut self, jobs: Vec<CompileJob>) -> Result<()> {
+        let mut pending = VecDeque::new();
+        for job in jobs {
+            let key = job.object.display().to_string();
+            if !needs_compile(
+                &job.source,
+                &job.object,
+                &job.command_line,
+                &job.tool.identity,
+                self.state.objects.get(&key),
+            ) {
+                continue;
             }
 
-            info!("Dependency graph: {:?}", self.dependency_graph);
+            // The object will be outdated until the compilation succeeds
+            self.state.objects.remove(&key);
+
+            // Mirror the source directory structure
+            let object_dir = job.object.parent().unwrap();
+            fs::create_dir_all(object_dir).map_err(|e| {
+                Error::CannotCreate(object_dir.to_path_buf(), e)
+            })?;
+
+            pending.push_back(job);
+        }
 
-            // Update the sources last modification time traversing the graph
-            // (DFS) for each his dependencies and taking the last
-            // time
-            let mut visited = vec![false; size];
-            for src_idx in 0..self.sources.len() {
-                // Mark all vertices as not visited
-                visited.fill(false);
+        let keep_going = self.keep_going;
+        let base = &self.config.working_dir;
+        let format = self.config.cli.message_format;
+        let (results, _) = pool::run(
+            pending,
+            self.jobs,
+            |job| (job.object.display().to_string(), job.run(base, format)),
+            |(_, result)| result.is_err() && !keep_going,
+        );
 
-                // Create a stack for the DFS
-                let mut stack = VecDeque::new();
-                stack.push_front(src_idx);
+        // Record how the objects have been generated and group the sources
+        // that failed to compile
+        let mut result = Ok(());
+        let mut failed = Vec::new();
+        for (key, object_result) in results {
+            match object_result {
+                Ok(object_state) => {
+                    self.state.objects.insert(key, object_state);
+                },
+                Err(Error::Compilation(mut sources)) => {
+                    failed.append(&mut sources)
+                },
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(_) => {},
+            }
+        }
+        if result.is_ok() && !failed.is_empty() {
+            failed.sort();
+            result = Err(Error::Compilation(failed));
+        }
 
-                // Set the track of the max `SystemTime` detected
-                let old_modif = self.sources[src_idx].modif;
+        // Store the state even on failure so the objects already compiled
+        // are not compiled again
+        self.state.save(&self.out_dir)?;
 
-                while !stack.is_empty() {
-                    let i = stack.pop_front().unwrap();
+        result
+    }
 
-                    // Check if this node has already been visited
-                    if visited[i] {
-                        continue;
-                    }
+    /// Set the maximum number of compilers running at the same time
+    #[inline]
+    pub fn jobs(&mut self, jobs: usize) -> &mut Build<'a> {
+        self.jobs = jobs.max(1);
+        self
+    }
 
-                    // Set as visited
-                    visited[i] = true;
-
-                    // Update last_time (index `sources` if `0 < i <
-                    // sources.len()`), otherwise access
-                    // `headers`
-                    let child_modif = if i < self.sources.len() {
-                        self.sources[i].modif
-                    } else {
-                        self.headers[i - self.sources.len()].modif
-                    };
-                    if self.sources[src_idx].modif < child_modif {
-                        self.sources[src_idx].modif = child_modif;
-                    }
+    /// Keep compiling the rest of sources when one of them fails
+    #[inline]
+    pub fn keep_going(&mut self, keep_going: bool) -> &mut Build<'a> {
+        self.keep_going = keep_going;
+        self
+    }
 
-                    // Push the childs of the current node to the stack
-                    stack.extend(&self.dependency_graph[i]);
-                }
+    /// Remove the objects (and their depfiles) of the last build whose
+    /// source no longer exists, the objects of the tests are left alone
+    fn remove_stale_objects(&mut self) -> Result<()> {
+        let obj_dir = self.out_dir.join("obj");
+        let objects = self
+            .sources
+            .iter()
+            .map(|s| self.object_path(s).display().to_string())
+            .collect::<Vec<String>>();
+        let stale = self
+            .state
+            .objects
+            .keys()
+            .filter(|object| {
+                Path::new(object).starts_with(&obj_dir)
+                    && !objects.contains(object)
+            })
+            .cloned()
+            .collect::<Vec<String>>();
 
-                if old_modif != self.sources[src_idx].modif {
-                    info!(
-                        "New `{:?}` last modif time: {:?}",
-                        self.sources[src_idx].modif.elapsed().unwrap(),
-                        old_modif.elapsed().unwrap()
-                    );
+        for object in stale {
+            info!("Removing stale object {:?}", object);
+            self.state.objects.remove(&object);
+
+            let object = PathBuf::from(object);
+            for path in [object.with_extension("d"), object] {
+                if path.is_file() {
+                    fs::remove_file(&path)
+                        .map_err(|e| Error::CannotRemove(path.clone(), e))?;
                 }
             }
+        }
+
+        Ok(())
+    }
 
-            // Filter from the sources all of them with a modification time
-            // lower than the modification time of the last build
-            self.sources.retain(|src| src.modif > last_time);
+    /// The path of the object of `source`, that mirrors the path of the
+    /// source relative to the project under `target/<profile>/obj`, so
+    /// `src/a/util.c` is compiled to `target/<profile>/obj/src/a/util.c.o`
+    fn object_path(&self, source: &Source) -> PathBuf {
+        let relative_path = source
+            .path
+            .strip_prefix(&self.config.working_dir)
+            .unwrap_or_else(|_| Path::new(source.path.file_name().unwrap()));
+        let mut object = self.out_dir.join("obj").join(relative_path);
+        object.as_mut_os_string().push(".o");
+
+        object
+    }
+
+    /// The path of the artifact generated by the project, depends on its
+    /// `ProjectType`
+    pub fn target_path(&self) -> PathBuf {
+        let project = &self.manifest.project;
+        let family = self.tool(project.language).family;
+
+        match project.kind {
+            ProjectType::StaticLib => {
+                self.out_dir.join(family.static_lib_name(&project.name))
+            },
+            ProjectType::DynamicLib => self.out_dir.join(
+                &family.dynamic_lib_names(&project.name, &project.version)[0],
+            ),
+            ProjectType::HeaderOnly => {
+                let extension = match project.language {
+                    Language::C => "h",
+                    Language::Cpp => "hpp",
+                };
+                self.out_dir.join(&project.name).with_extension(extension)
+            },
+            _ => self
+                .out_dir
+                .join(&project.name)
+                .with_extension(EXE_EXTENSION),
         }
+    }
 
-        // Compile all the sources and place them in `self.out_dir` the
-        // already configured tool will take care of providing a correct
-        // command
-        //
-        // TODO: Compile in parallel according to the avaible threads
-        let mut childs = Vec::new();
-        for chunk in self.sources.chunks(4) {
-            for source in chunk {
-                let mut command = self.tool.to_build_command(&self.header_dirs);
-
-                // FIXME: Maybe no need to specify "-o <source_name>.o" to the
-                // compiler
-                let out_file = self.out_dir.join(
-                    Path::new(source.path.file_name().unwrap())
-                        .with_extension("o"),
-                );
+    /// Group all the headers into a single `target/<profile>/<name>.h` (if
+    /// needed) for header-only projects, returns a boolean indicating if it
+    /// was needed to generate it or not
+    ///
+    /// The headers are ordered so every header goes after its dependencies
+    /// and the `#include "<header>"` of the project headers are removed as
+    /// they are already inlined
+    pub fn amalgamate(&mut self) -> Result<bool> {
+        let project_name = &self.manifest.project.name;
+        let target_path = self.target_path();
+
+        // If the single header exists, it was generated from the same headers
+        // and its up to date do not regenerate it
+        let mut headers = self
+            .headers
+            .iter()
+            .map(|h| h.path.clone())
+            .collect::<Vec<PathBuf>>();
+        headers.sort();
+        if let Some(last_time) = self.last_time {
+            if self.state.headers == headers
+                && self.headers.iter().all(|h| h.modif < last_time)
+            {
+                self.emit_artifact(true);
+                return Ok(false);
+            }
+        }
 
-                info!("Compiling {:?}", source);
+        self.build_dependency_graph()?;
 
-                let cmd = command.arg(&out_file).arg(&source.path);
-                childs.push(cmd.spawn().map_err(|e| {
-                    Error::ProcessCreation(self.tool.path.clone(), e)
-                })?);
+        // The entry headers are the ones no other header includes
+        let mut included = vec![false; self.headers.len()];
+        for deps in &self.dependency_graph {
+            deps.iter().for_each(|&i| included[i] = true);
+        }
 
-                // Wait for each thread to finish
-                for child in childs.iter_mut() {
-                    if !child.wait().map_err(Error::ProcessExec)?.success() {
-                        return Err(Error::Compilation);
-                    }
+        // Topological order through a post-order DFS starting from the entry
+        // headers (and then from the rest in case of include cycles)
+        let mut visited = vec![false; self.headers.len()];
+        let mut order = Vec::new();
+        let roots = (0..self.headers.len())
+            .filter(|&i| !included[i])
+            .chain(0..self.headers.len())
+            .collect::<Vec<usize>>();
+        for root in roots {
+            // The stack contains the node and if its dependencies have
+            // already been pushed
+            let mut stack = vec![(root, false)];
+            while let Some((i, expanded)) = stack.pop() {
+                if expanded {
+                    order.push(i);
+                    continue;
+                }
+                if visited[i] {
+                    continue;
                 }
+                visited[i] = true;
+
+                stack.push((i, true));
+                stack.extend(
+                    self.dependency_graph[i]
+                        .iter()
+                        .rev()
+                        .filter(|&&d| !visited[d])
+                        .map(|&d| (d, false)),
+                );
             }
         }
 
-        Ok(self)
+        info!("Amalgamation order: {:?}", order);
+
+        // Generate the single header surrounded by an include guard
+        let guard = format!(
+            "{}_H",
+            project_name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                })
+                .collect::<String>()
+        );
+        let mut single_header = format!("#ifndef {0}\n#define {0}\n", guard);
+        for i in order {
+            let header = &self.headers[i];
+            let data = fs::read_to_string(&header.path)
+                .map_err(|e| Error::CannotRead(header.path.clone(), e))?;
+            let relative_path = header
+                .path
+                .strip_prefix(&self.config.working_dir)
+                .unwrap_or(&header.path);
+
+            single_header
+                .push_str(&format!("\n// {}\n", relative_path.display()));
+            for line in data.lines() {
+                // Skip the includes of headers that are already inlined
+                let inlined = quoted_include(line).is_some_and(|name| {
+                    self.project_header(&header.path, name).is_some()
+                });
+                if !inlined {
+                    single_header.push_str(line);
+                    single_header.push('\n');
+                }
+            }
+        }
+        single_header.push_str(&format!("\n#endif // {}\n", guard));
+
+        info!("Generating single header {:?}", &target_path);
+
+        fs::write(&target_path, single_header)
+            .map_err(|e| Error::CannotCreate(target_path, e))?;
+
+        // Record which headers have been grouped
+        self.state.headers = headers;
+        self.state.save(&self.out_dir)?;
+        self.emit_artifact(false);
+
+        Ok(true)
     }
 
     /// Links the objects (if needed) and returns a boolean indicating if it
     /// wasn't needed to link the executable or not
     pub fn link(&mut self) -> Result<bool> {
-        let project_name = &self.config.config.as_ref().unwrap().project.name;
-
-        // Extract all the objects again (but now they should be recompiled)
-        self.objects = Object::from_dir(&self.out_dir)?;
+        let manifest = self.manifest;
+        let project = &manifest.project;
+        let tool = self.link_tool(self.link_language)?;
+
+        // The objects of the current sources (now they should be compiled),
+        // not every object on `out_dir` as there can be stray ones
+        self.objects = self
+            .sources
+            .iter()
+            .map(|source| {
+                let path = self.object_path(source);
+                modified(&path).map(|modif| Object::from((path, modif)))
+            })
+            .collect::<Result<Vec<Object>>>()?;
+
+        // The libraries of the dependencies go after the objects, except on
+        // static libraries as they are linked by whoever links the library
+        let mut inputs = self.objects.clone();
+        if project.kind != ProjectType::StaticLib {
+            inputs.extend(self.dependency_libraries()?);
+        }
 
         // Generate the path of the existing (or not) target to generate
-        let target_path = self
-            .out_dir
-            .join(project_name)
-            .with_extension(EXE_EXTENSION);
-
-        // If the executable exist and its up to date do not recompile
-        if target_path.is_file() {
-            let target_path_modif =
-                target_path.metadata().unwrap().modified().unwrap();
-            let objects_max_modif =
-                self.objects.iter().map(|o| o.modif).max().unwrap();
-            if target_path_modif > objects_max_modif {
+        let target_path = self.target_path();
+
+        // Link everything into an executable or a dynamic library, or archive
+        // it into a static library
+        let dynamic_lib_names = tool
+            .family
+            .dynamic_lib_names(&project.name, &project.version);
+        let (command, path) = match project.kind {
+            ProjectType::StaticLib => {
+                let archiver = Archiver::new(tool.family)?;
+                info!("Selected archiver: {:?}", &archiver);
+                (
+                    archiver.to_archive_command(&target_path, &self.objects),
+                    archiver.path,
+                )
+            },
+            ProjectType::DynamicLib => {
+                let soname =
+                    dynamic_lib_names.get(1).unwrap_or(&dynamic_lib_names[0]);
+                let import_lib = tool
+                    .family
+                    .import_lib_name(&project.name)
+                    .map(|name| self.out_dir.join(name));
+                (
+                    tool.to_shared_link_command(
+                        &target_path,
+                        Some(soname),
+                        import_lib.as_deref(),
+                        &inputs,
+                    ),
+                    tool.path.clone(),
+                )
+            },
+            _ => (
+                tool.to_link_command(&target_path, &inputs),
+                tool.path.clone(),
+            ),
+        };
+        let link_state = LinkState {
+            target: target_path.clone(),
+            command: state::command_line(&command),
+        };
+
+        // If the target exist, it was generated the same way and its up to
+        // date do not link it again
+        if target_path.is_file()
+            && self.state.link.as_ref() == Some(&link_state)
+        {
+            let target_path_modif = modified(&target_path)?;
+            let objects_max_modif = inputs.iter().map(|o| o.modif).max();
+            if objects_max_modif.is_none_or(|m| target_path_modif > m) {
+                self.emit_artifact(true);
                 return Ok(false);
             }
         }
 
         info!("Linking {:?}", &target_path);
 
-        // Link everything into an executable
-        //
-        // TODO: Capture output and parse it
-        let mut command = self.tool.to_link_command(target_path, &self.objects);
-        command
-            .status()
-            .map_err(|e| Error::ProcessCreation(self.tool.path.clone(), e))?;
+        // The archiver just appends objects, so remove the old one to not
+        // keep objects of deleted sources
+        if project.kind == ProjectType::StaticLib && target_path.is_file() {
+            fs::remove_file(&target_path)
+                .map_err(|e| Error::CannotRemove(target_path.clone(), e))?;
+        }
+
+        run_link(command, path, &target_path)?;
+
+        // Record how the target has been generated
+        self.state.link = Some(link_state);
+        self.state.save(&self.out_dir)?;
+
+        // Create the soname and the unversioned symlinks of the dynamic
+        // library
+        #[cfg(unix)]
+        if project.kind == ProjectType::DynamicLib {
+            for link_name in &dynamic_lib_names[1..] {
+                let link_path = self.out_dir.join(link_name);
+                if fs::symlink_metadata(&link_path).is_ok() {
+                    fs::remove_file(&link_path).map_err(|e| {
+                        Error::CannotRemove(link_path.clone(), e)
+                    })?;
+                }
+                std::os::unix::fs::symlink(&dynamic_lib_names[0], &link_path)
+                    .map_err(|e| Error::CannotCreate(link_path, e))?;
+            }
+        }
+        self.emit_artifact(false);
 
         Ok(true)
     }
+
+    /// Compile and link the programs of `tests_dir` against the objects of
+    /// the project (except the ones that define `main`), the sources of the
+    /// project must be already compiled
+    ///
+    /// Everything goes to `target/<profile>/tests`, the test cases declared
+    /// with `TEST(name)` are run by a generated source that includes the test
+    /// and is compiled instead of it
+    pub fn tests<P: AsRef<Path>>(
+        &mut self,
+        tests_dir: P,
+    ) -> Result<Vec<TestProgram>> {
+        self.harness_programs(tests_dir, "tests", &TEST_HARNESS)
+    }
+
+    /// Compile and link the benchmarks of `benches_dir` like the tests, to
+    /// `target/<profile>/benches` and with the bench harness
+    pub fn benches<P: AsRef<Path>>(
+        &mut self,
+        benches_dir: P,
+    ) -> Result<Vec<TestProgram>> {
+        self.harness_programs(benches_dir, "benches", &BENCH_HARNESS)
+    }
+
+    /// Compile and link the programs of `dir` to `target/<profile>/<name>`
+    /// with the `harness`
+    fn harness_programs<P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        name: &str,
+        harness: &Harness,
+    ) -> Result<Vec<TestProgram>> {
+        let dir = self.config.working_dir.join(dir);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let tests_out_dir = self.out_dir.join(name);
+        let harness_dir = tests_out_dir.join("harness");
+        harness.write_header(&harness_dir)?;
+        let mut include_dirs = self.header_dirs.clone();
+        include_dirs.push(harness_dir.clone());
+
+        let library_objects = self.library_objects()?;
+
+        let mut jobs = Vec::new();
+        let mut commands = Vec::new();
+        let mut programs = Vec::new();
+        let mut test_sources = Source::from_dir(&dir)?;
+        test_sources.sort_by(|a, b| a.path.cmp(&b.path));
+        for test_source in test_sources {
+            let test_path = test_source.path.clone();
+            let relative_path =
+                test_source.path.strip_prefix(&dir).unwrap().to_path_buf();
+            let cases = harness.discover(&test_source.path)?;
+            let language = test_source.language();
+            let source = if cases.is_empty() {
+                test_source
+            } else {
+                let runner = harness_dir.join(&relative_path);
+                harness.write_runner(&runner, &test_source.path, &cases)?;
+                let modif = modified(&runner)?;
+                Source::from((runner, modif))
+            };
+
+            let mut object = tests_out_dir.join("obj").join(&relative_path);
+            object.as_mut_os_string().push(".o");
+            let job = self.compile_job(source, object.clone(), &include_dirs)?;
+
+            // The generated runner is listed as the source it includes, so
+            // the editors know how to compile it (and find the harness)
+            let mut command = self.compile_command(&job);
+            if command.file != test_path {
+                let runner = command.file.to_string_lossy().into_owned();
+                for argument in &mut command.arguments {
+                    if *argument == runner {
+                        *argument = test_path.to_string_lossy().into_owned();
+                    }
+                }
+                command.file = test_path;
+            }
+            commands.push(command);
+            jobs.push(job);
+
+            programs.push((
+                TestProgram {
+                    name: relative_path
+                        .with_extension("")
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                    path: tests_out_dir
+                        .join(&relative_path)
+                        .with_extension(EXE_EXTENSION),
+                    cases,
+                },
+                object,
+                language,
+            ));
+        }
+        self.record_commands(commands)?;
+        self.run_jobs(jobs)?;
+
+        // Link every program if any of its objects changed
+        let mut result = Vec::new();
+        for (program, object, language) in programs {
+            let mut objects = library_objects.clone();
+            objects.push(object);
+            self.link_program(&program.path, &objects, language)?;
+
+            result.push(program);
+        }
+
+        Ok(result)
+    }
+
+    /// Compile and link the examples of `examples_dir` against the objects of
+    /// the project (except the ones that define `main`), the sources of the
+    /// project must be already compiled
+    ///
+    /// Every source of `examples_dir` is an example, and so is every
+    /// subdirectory with all the sources inside it, they are linked to
+    /// `target/<profile>/examples/<name>`
+    ///
+    /// Returns `true` if any example has been linked again
+    pub fn examples<P: AsRef<Path>>(
+        &mut self,
+        examples_dir: P,
+        selection: Examples,
+    ) -> Result<bool> {
+        let dir = self.config.working_dir.join(examples_dir);
+        let mut entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .map(|e| e.map(|e| e.path()))
+                .collect::<std::io::Result<Vec<PathBuf>>>()
+                .map_err(|e| Error::CannotRead(dir.clone(), e))?,
+            Err(_) => Vec::new(),
+        };
+        entries.sort();
+
+        // Find the sources of every example
+        let mut examples = Vec::new();
+        for entry in entries {
+            let name = match entry.file_stem().and_then(|s| s.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if let Examples::Only(only) = selection {
+                if name != only {
+                    continue;
+                }
+            }
+
+            let sources = if entry.is_dir() {
+                Source::from_dir(&entry)?
+            } else if entry
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| Source::EXTS.contains(&e))
+            {
+                let modif = modified(&entry)?;
+                vec![Source::from((entry, modif))]
+            } else {
+                continue;
+            };
+            examples.push((name, sources));
+        }
+        if let Examples::Only(only) = selection {
+            if examples.is_empty() {
+                return Err(Error::UnknownExample(only.to_string()));
+            }
+        }
+
+        let library_objects = self.library_objects()?;
+        let examples_out_dir = self.out_dir.join("examples");
+        let header_dirs = self.header_dirs.clone();
+
+        let mut jobs = Vec::new();
+        let mut programs = Vec::new();
+        for (name, sources) in examples {
+            let mut objects = library_objects.clone();
+            let mut language = Language::C;
+            for source in sources {
+                let relative_path =
+                    source.path.strip_prefix(&dir).unwrap().to_path_buf();
+                let mut object =
+                    examples_out_dir.join("obj").join(&relative_path);
+                object.as_mut_os_string().push(".o");
+                if source.language() == Language::Cpp {
+                    language = Language::Cpp;
+                }
+
+                objects.push(object.clone());
+                jobs.push(self.compile_job(source, object, &header_dirs)?);
+            }
+
+            let path =
+                examples_out_dir.join(&name).with_extension(EXE_EXTENSION);
+            programs.push((name, path, objects, language));
+        }
+        let commands = jobs.iter().map(|job| self.compile_command(job));
+        self.record_commands(commands.collect())?;
+        self.run_jobs(jobs)?;
+
+        let mut changes = false;
+        for (name, path, objects, language) in programs {
+            let linked = self.link_program(&path, &objects, language)?;
+            if self.config.cli.message_format == MessageFormat::Json {
+                Message::CompilerArtifact {
+                    target: &name,
+                    kind: ProjectType::Binary,
+                    filenames: vec![path],
+                    fresh: !linked,
+                }
+                .emit();
+            }
+            changes |= linked;
+        }
+
+        Ok(changes)
+    }
+
+    /// The objects of the sources of the project that don't define `main`,
+    /// the ones the tests and examples are linked against
+    fn library_objects(&self) -> Result<Vec<PathBuf>> {
+        let mut objects = Vec::new();
+        for source in &self.sources {
+            if !defines_main(&source.path)? {
+                objects.push(self.object_path(source));
+            }
+        }
+
+        Ok(objects)
+    }
+
+    /// Link the `objects` into the executable at `path` (if any of them is
+    /// newer or it was linked in another way), with the C++ driver if the
+    /// program or the project is C++, returns `true` if it has been linked
+    fn link_program(
+        &mut self,
+        path: &Path,
+        objects: &[PathBuf],
+        language: Language,
+    ) -> Result<bool> {
+        let language = match self.link_language {
+            Language::Cpp => Language::Cpp,
+            Language::C => language,
+        };
+        let tool = self.link_tool(language)?;
+        let mut objects = objects
+            .iter()
+            .map(|path| {
+                modified(path).map(|modif| Object::from((path.clone(), modif)))
+            })
+            .collect::<Result<Vec<Object>>>()?;
+        objects.extend(self.dependency_libraries()?);
+
+        let command = tool.to_link_command(path, &objects);
+        let link_state = LinkState {
+            target: path.to_path_buf(),
+            command: state::command_line(&command),
+        };
+
+        // If the program exists, it was linked the same way and its up to
+        // date do not link it again
+        let key = path.display().to_string();
+        if path.is_file() && self.state.programs.get(&key) == Some(&link_state)
+        {
+            let program_modif = modified(path)?;
+            if objects.iter().all(|o| program_modif > o.modif) {
+                return Ok(false);
+            }
+        }
+
+        info!("Linking {:?}", path);
+
+        let program_dir = path.parent().unwrap();
+        fs::create_dir_all(program_dir)
+            .map_err(|e| Error::CannotCreate(program_dir.to_path_buf(), e))?;
+        run_link(command, tool.path.clone(), path)?;
+
+        // Record how the program has been linked
+        self.state.programs.insert(key, link_state);
+        self.state.save(&self.out_dir)?;
+
+        Ok(true)
+    }
+
+    /// The paths of the generated target and its symlinks and import library
+    /// (if any)
+    pub fn artifacts(&self) -> Vec<PathBuf> {
+        let project = &self.manifest.project;
+        let family = self.tool(project.language).family;
+        let mut filenames = vec![self.target_path()];
+        if project.kind == ProjectType::DynamicLib {
+            #[cfg(unix)]
+            filenames.extend(
+                family.dynamic_lib_names(&project.name, &project.version)[1..]
+                    .iter()
+                    .map(|name| self.out_dir.join(name)),
+            );
+            filenames.extend(
+                family
+                    .import_lib_name(&project.name)
+                    .map(|name| self.out_dir.join(name)),
+            );
+        }
+
+        filenames
+    }
+
+    /// Emit the message of the generated target (and its symlinks and
+    /// import library) when the messages are in JSON, `fresh` if it was
+    /// already up to date
+    fn emit_artifact(&self, fresh: bool) {
+        if self.config.cli.message_format != MessageFormat::Json {
+            return;
+        }
+
+        let project = &self.manifest.project;
+        Message::CompilerArtifact {
+            target: &project.name,
+            kind: project.kind,
+            filenames: self.artifacts(),
+            fresh,
+        }
+        .emit();
+    }
 }
@@ -0,0 +1,63 @@
+//! The compilation database (`compile_commands.json`) used by tools like
+//! clangd or clang-tidy to know how every source of the project is compiled
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::*;
+
+/// Name of the compilation database at the root of the project
+pub(crate) const COMPDB_FILE: &str = "compile_commands.json";
+
+/// How a single source is compiled
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct CompileCommand {
+    /// The working directory of the compilation
+    pub directory: PathBuf,
+
+    /// The source compiled
+    pub file: PathBuf,
+
+    /// The full command line, the compiler included
+    pub arguments: Vec<String>,
+
+    /// The object generated
+    pub output: PathBuf,
+}
+
+/// Update the compilation database at `dir` with the `commands`, they
+/// replace the ones of the same sources and the sources that no longer exist
+/// are removed, the file is only touched if its contents changed so tools
+/// watching it don't reload it on every build
+pub(crate) fn update(
+    dir: &Path,
+    mut commands: Vec<CompileCommand>,
+) -> Result<()> {
+    let path = dir.join(COMPDB_FILE);
+    let old_data = fs::read_to_string(&path).ok();
+
+    // The database is shared by the sources of the project, the tests, the
+    // benchmarks and the examples that are compiled on different commands
+    let old_commands: Vec<CompileCommand> = old_data
+        .as_deref()
+        .and_then(|data| serde_json::from_str(data).ok())
+        .unwrap_or_default();
+    for command in old_commands {
+        if command.file.is_file()
+            && !commands.iter().any(|c| c.file == command.file)
+        {
+            commands.push(command);
+        }
+    }
+    commands.sort_by(|a, b| a.file.cmp(&b.file));
+
+    let mut data = serde_json::to_string_pretty(&commands).unwrap();
+    data.push('\n');
+    if old_data.as_deref() == Some(&data[..]) {
+        return Ok(());
+    }
+
+    fs::write(&path, data).map_err(|e| Error::CannotCreate(path, e))
+}
@@ -1,9 +1,17 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use console::style;
+
+use crate::error::{Error, Result};
 
 /// The main cli of the app
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[clap(author="@cdecompilador", 
        version,
        about="Easy to use c build system", 
@@ -13,17 +21,432 @@ pub(crate) struct Cli {
     /// `new`.
     #[clap(subcommand)]
     pub commands: Command,
+
+    /// How to print the progress and the diagnostics of the build
+    #[clap(
+        long,
+        arg_enum,
+        global = true,
+        default_value_t = MessageFormat::Human
+    )]
+    pub message_format: MessageFormat,
+
+    /// Path to the `Amargo.toml` of the project, by default the nearest one
+    /// on the current directory or its parents
+    #[clap(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
 }
 
 /// The configurations extracted from the `Amargo.toml`
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Config {
     pub project: Project,
+
+    /// The `[build]` table
+    #[serde(default, skip_serializing_if = "BuildConfig::is_empty")]
+    pub build: BuildConfig,
+
+    /// The `[test]` table
+    #[serde(default, skip_serializing_if = "TestConfig::is_empty")]
+    pub test: TestConfig,
+
+    /// The `[profile.<name>]` tables, `debug` and `release` always exist
+    /// and any other name is a custom profile
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, Profile>,
+
+    /// The `[dependencies]` table, the amargo projects this one depends on
+    /// by their name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, Dependency>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Project {
     pub name: String,
+
+    /// Version of the project in the form `major.minor.patch`, used to
+    /// version the dynamic libraries
+    #[serde(default = "Project::default_version")]
+    pub version: String,
+
+    /// What `amargo build` produces, a binary by default
+    #[serde(rename = "type", default)]
+    pub kind: ProjectType,
+
+    /// The language of the project, C by default
+    #[serde(default)]
+    pub language: Language,
+
+    /// The C standard like "c99" or "c11", the compiler default if missing
+    #[serde(
+        rename = "c-standard",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub c_standard: Option<String>,
+
+    /// The C++ standard like "c++17" or "c++20", the compiler default if
+    /// missing
+    #[serde(
+        rename = "cpp-standard",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub cpp_standard: Option<String>,
+}
+
+/// Settings of how the project is built
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub struct BuildConfig {
+    /// Maximum number of compilers running at the same time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+}
+
+/// A dependency on another amargo project, a library built with the profile
+/// of the project that depends on it
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Dependency {
+    /// The root of the project, relative to the one that depends on it
+    pub path: PathBuf,
+}
+
+impl BuildConfig {
+    /// Check if nothing has been configured
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_none()
+    }
+}
+
+/// Settings of how `amargo test` runs the tests
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub struct TestConfig {
+    /// Seconds a test can run before it's killed and considered failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// The timeout in seconds of specific tests by their name, like
+    /// `"math::slow_case" = 300`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub timeouts: BTreeMap<String, u64>,
+}
+
+impl TestConfig {
+    /// Check if nothing has been configured
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none() && self.timeouts.is_empty()
+    }
+}
+
+impl Project {
+    /// The version of new projects or of the ones that don't provide it
+    pub fn default_version() -> String {
+        String::from("0.1.0")
+    }
+
+    /// Check that `name` can be used as the name of a project (or a
+    /// profile), it names files and directories so it's restricted to
+    /// letters, digits, `_` and `-`, starting with a letter or `_`
+    pub fn validate_name(name: &str) -> std::result::Result<(), String> {
+        let first = name.chars().next();
+        if first.is_none() {
+            Err("the name is empty".into())
+        } else if !first.is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+            Err(format!("`{}` must start with a letter or `_`", name))
+        } else if let Some(c) = name
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        {
+            Err(format!(
+                "`{}` contains `{}`, only letters, digits, `_` and `-` are \
+                 allowed",
+                name, c
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The keys known on the tables of the `Amargo.toml` by their path, a `*`
+/// matches any key and the tables not listed accept any key
+const KNOWN_KEYS: &[(&str, &[&str])] = &[
+    ("", &["project", "build", "test", "profile", "dependencies"]),
+    (
+        "project",
+        &[
+            "name",
+            "version",
+            "type",
+            "language",
+            "c-standard",
+            "cpp-standard",
+        ],
+    ),
+    ("build", &["jobs"]),
+    ("dependencies.*", &["path"]),
+    ("test", &["timeout", "timeouts"]),
+    (
+        "profile.*",
+        &[
+            "inherits",
+            "opt-level",
+            "debug",
+            "defines",
+            "cflags",
+            "cxxflags",
+            "ldflags",
+            "warnings-as-errors",
+        ],
+    ),
+];
+
+/// Warn about the keys of `table` (at `path`) and its subtables that amargo
+/// doesn't know, suggesting the known key with the closest name
+fn warn_unknown_keys(table: &toml::value::Table, path: &[&str]) {
+    let known = KNOWN_KEYS.iter().find_map(|(pattern, keys)| {
+        let pattern = pattern.split('.').filter(|p| !p.is_empty());
+        let matches = pattern.clone().count() == path.len()
+            && pattern.zip(path).all(|(p, k)| p == "*" || p == *k);
+        matches.then_some(*keys)
+    });
+
+    for (key, value) in table {
+        let full_key = path
+            .iter()
+            .copied()
+            .chain(std::iter::once(&key[..]))
+            .collect::<Vec<_>>();
+        if known.is_some_and(|known| !known.contains(&&key[..])) {
+            let suggestion = known
+                .unwrap()
+                .iter()
+                .map(|k| (strsim::levenshtein(key, k), k))
+                .filter(|(distance, _)| *distance <= 3)
+                .min();
+            let mut message =
+                format!("unused manifest key `{}`", full_key.join("."));
+            if let Some((_, suggestion)) = suggestion {
+                message.push_str(&format!(", did you mean `{}`?", suggestion));
+            }
+            eprintln!("{} {}", style("warning:").yellow().bold(), message);
+            continue;
+        }
+
+        if let toml::Value::Table(table) = value {
+            warn_unknown_keys(table, &full_key);
+        }
+    }
+}
+
+impl Config {
+    /// Read and validate the `Amargo.toml` at `path`, the syntax errors, the
+    /// values of the wrong type and the invalid names or versions are errors
+    /// and the unknown keys are warnings
+    pub fn load(path: &Path) -> Result<Config> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| Error::CannotRead(path.to_path_buf(), e))?;
+        let invalid =
+            |message: String| Error::InvalidManifest(path.into(), message);
+
+        // First the syntax, then the keys and finally the schema, the errors
+        // of the parser already have the line and column
+        let value = data
+            .parse::<toml::Value>()
+            .map_err(|e| invalid(e.to_string()))?;
+        if let toml::Value::Table(table) = &value {
+            warn_unknown_keys(table, &[]);
+        }
+        let config = toml::from_str::<Config>(&data)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        Project::validate_name(&config.project.name)
+            .map_err(|e| invalid(format!("invalid `project.name`: {}", e)))?;
+        let version = config.project.version.split('.').collect::<Vec<_>>();
+        if version.len() != 3
+            || version.iter().any(|v| v.parse::<u64>().is_err())
+        {
+            return Err(invalid(format!(
+                "invalid `project.version` `{}`, expected \
+                 `major.minor.patch` like \"0.1.0\"",
+                config.project.version
+            )));
+        }
+        if config.build.jobs == Some(0) {
+            return Err(invalid("`build.jobs` must be at least 1".into()));
+        }
+        for name in config.profile.keys() {
+            Project::validate_name(name).map_err(|e| {
+                invalid(format!("invalid profile name: {}", e))
+            })?;
+
+            // `target/bench` has the results of the benchmarks
+            if name == "bench" {
+                return Err(invalid(
+                    "the profile name `bench` is reserved".into(),
+                ));
+            }
+        }
+        for name in config.dependencies.keys() {
+            Project::validate_name(name).map_err(|e| {
+                invalid(format!("invalid dependency name: {}", e))
+            })?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// The compiler and linker settings of a profile, every field is optional
+/// on the `Amargo.toml`, the missing ones are taken from the profile it
+/// inherits from (the defaults of the `BuildType` for `debug` and `release`)
+#[derive(Default, Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Profile {
+    /// The base profile, mandatory for custom profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherits: Option<String>,
+
+    /// Optimization level: 0, 1, 2, 3, "s" or "z"
+    pub opt_level: Option<OptLevel>,
+
+    /// Generate debug info
+    pub debug: Option<bool>,
+
+    /// Preprocessor defines in the form `NAME` or `NAME=VALUE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub defines: Vec<String>,
+
+    /// Extra arguments passed to the C compiler
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cflags: Vec<String>,
+
+    /// Extra arguments passed to the C++ compiler
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cxxflags: Vec<String>,
+
+    /// Extra arguments passed to the linker
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ldflags: Vec<String>,
+
+    /// Turn all the warnings into errors
+    pub warnings_as_errors: Option<bool>,
+}
+
+impl Profile {
+    /// The default settings of each `BuildType`
+    pub fn new(mode: BuildType) -> Self {
+        match mode {
+            BuildType::Debug => Profile {
+                opt_level: Some(OptLevel::O0),
+                debug: Some(true),
+                warnings_as_errors: Some(false),
+                ..Default::default()
+            },
+            BuildType::Release => Profile {
+                opt_level: Some(OptLevel::O3),
+                debug: Some(false),
+                warnings_as_errors: Some(false),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Fill the settings not provided on `self` with the ones of `parent`,
+    /// the flags and defines of the `parent` go first
+    pub fn inherit(&mut self, parent: &Profile) {
+        self.opt_level = self.opt_level.or(parent.opt_level);
+        self.debug = self.debug.or(parent.debug);
+        self.warnings_as_errors =
+            self.warnings_as_errors.or(parent.warnings_as_errors);
+
+        let mut defines = parent.defines.clone();
+        defines.append(&mut self.defines);
+        self.defines = defines;
+
+        let mut cflags = parent.cflags.clone();
+        cflags.append(&mut self.cflags);
+        self.cflags = cflags;
+
+        let mut cxxflags = parent.cxxflags.clone();
+        cxxflags.append(&mut self.cxxflags);
+        self.cxxflags = cxxflags;
+
+        let mut ldflags = parent.ldflags.clone();
+        ldflags.append(&mut self.ldflags);
+        self.ldflags = ldflags;
+    }
+
+    /// Short description of what the profile does like
+    /// "optimized + debuginfo"
+    pub fn summary(&self) -> String {
+        let mut summary = match self.opt_level {
+            Some(OptLevel::O0) | None => String::from("unoptimized"),
+            _ => String::from("optimized"),
+        };
+        if self.debug == Some(true) {
+            summary.push_str(" + debuginfo");
+        }
+
+        summary
+    }
+}
+
+/// The optimization levels, on the `Amargo.toml` they are written as an
+/// integer (0-3) or as a string ("s" optimize for size, "z" aggressively for
+/// size)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(into = "toml::Value")]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    Size,
+    MinSize,
+}
+
+impl From<OptLevel> for toml::Value {
+    fn from(opt_level: OptLevel) -> toml::Value {
+        match opt_level {
+            OptLevel::O0 => toml::Value::Integer(0),
+            OptLevel::O1 => toml::Value::Integer(1),
+            OptLevel::O2 => toml::Value::Integer(2),
+            OptLevel::O3 => toml::Value::Integer(3),
+            OptLevel::Size => toml::Value::String("s".into()),
+            OptLevel::MinSize => toml::Value::String("z".into()),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for OptLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        match toml::Value::deserialize(deserializer)? {
+            toml::Value::Integer(0) => Ok(OptLevel::O0),
+            toml::Value::Integer(1) => Ok(OptLevel::O1),
+            toml::Value::Integer(2) => Ok(OptLevel::O2),
+            toml::Value::Integer(3) => Ok(OptLevel::O3),
+            toml::Value::String(s) if s == "s" => Ok(OptLevel::Size),
+            toml::Value::String(s) if s == "z" => Ok(OptLevel::MinSize),
+            v => Err(D::Error::custom(format!(
+                "invalid opt-level `{}`, expected 0, 1, 2, 3, \"s\" or \"z\"",
+                v
+            ))),
+        }
+    }
+}
+
+/// Find the `Amargo.toml` of the project that contains `dir`, the nearest one
+/// on `dir` or its parents
+pub(crate) fn find_manifest(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join("Amargo.toml"))
+        .find(|manifest| manifest.is_file())
 }
 
 /// All the configs needed of the project to execute any subcommand in `amargo`
@@ -40,34 +463,199 @@ impl Default for ProjectConfig {
     }
 }
 
+impl ProjectConfig {
+    /// The `Amargo.toml` of the project, an error if there's no project
+    pub fn manifest(&self) -> Result<&Config> {
+        self.config
+            .as_ref()
+            .ok_or_else(|| Error::NotAProject(self.working_dir.clone()))
+    }
+
+    /// The maximum number of compilers running at the same time, in order of
+    /// priority: the `-j` of the cli, the `AMARGO_JOBS` environment variable,
+    /// the `[build] jobs` of the `Amargo.toml` and the available CPUs
+    pub fn jobs(&self, cli_jobs: Option<usize>) -> usize {
+        cli_jobs
+            .or_else(|| {
+                std::env::var("AMARGO_JOBS")
+                    .ok()
+                    .and_then(|j| j.parse().ok())
+            })
+            .or_else(|| self.config.as_ref().and_then(|c| c.build.jobs))
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |n| n.get())
+            })
+    }
+
+    /// The configs of the dependency `name` of the project, that must be a
+    /// library named like that
+    pub fn dependency(
+        &self,
+        name: &str,
+        dependency: &Dependency,
+    ) -> Result<ProjectConfig> {
+        let invalid =
+            |reason: String| Error::InvalidDependency(name.into(), reason);
+        let path = self.working_dir.join(&dependency.path);
+        let working_dir = path.canonicalize().map_err(|e| {
+            invalid(format!("cannot find `{}`: {}", path.display(), e))
+        })?;
+        let config = Config::load(&working_dir.join("Amargo.toml"))?;
+
+        if config.project.name != name {
+            return Err(invalid(format!(
+                "the project at `{}` is named `{}`",
+                working_dir.display(),
+                config.project.name
+            )));
+        }
+        if config.project.kind == ProjectType::Binary {
+            return Err(invalid(format!(
+                "`{}` is a binary, only libraries can be dependencies",
+                working_dir.display()
+            )));
+        }
+
+        Ok(ProjectConfig {
+            cli: self.cli.clone(),
+            config: Some(config),
+            working_dir,
+        })
+    }
+
+    /// How long the test `name` can run, the one of the `[test.timeouts]`
+    /// of the `Amargo.toml`, then the `[test] timeout` and finally 60 seconds
+    pub fn test_timeout(&self, name: &str) -> Duration {
+        let test = self.config.as_ref().map(|c| &c.test);
+        let seconds = test
+            .and_then(|t| t.timeouts.get(name).copied())
+            .or_else(|| test.and_then(|t| t.timeout))
+            .unwrap_or(60);
+
+        Duration::from_secs(seconds)
+    }
+
+    /// Get the settings of the profile `name`, that is the one on the
+    /// `Amargo.toml` (if any) filled with the profiles it inherits from and
+    /// finally with the defaults of `debug` or `release`
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        self.resolve_profile(name, &mut Vec::new())
+    }
+
+    /// Recursively resolve the profile `name`, `chain` contains the profiles
+    /// already visited to detect inheritance cycles
+    fn resolve_profile(
+        &self,
+        name: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<Profile> {
+        let profiles = &self.manifest()?.profile;
+
+        chain.push(name.to_string());
+        if chain[..chain.len() - 1].iter().any(|p| p == name) {
+            return Err(Error::InvalidProfile(
+                chain[0].clone(),
+                format!("inheritance cycle {}", chain.join(" -> ")),
+            ));
+        }
+
+        let profile = profiles.get(name).cloned();
+        let inherits = profile.as_ref().and_then(|p| p.inherits.clone());
+        let parent = match (name, inherits) {
+            ("debug" | "release", Some(_)) => {
+                return Err(Error::InvalidProfile(
+                    name.to_string(),
+                    "built-in profiles cannot use `inherits`".into(),
+                ))
+            },
+            ("debug", None) => Profile::new(BuildType::Debug),
+            ("release", None) => Profile::new(BuildType::Release),
+            (_, Some(parent)) => self.resolve_profile(&parent, chain)?,
+            (_, None) if profile.is_some() => {
+                return Err(Error::InvalidProfile(
+                    name.to_string(),
+                    "custom profiles must set `inherits`".into(),
+                ))
+            },
+            (_, None) => return Err(Error::UnknownProfile(name.to_string())),
+        };
+
+        let mut profile = profile.unwrap_or_default();
+        profile.inherit(&parent);
+
+        Ok(profile)
+    }
+}
+
 /// Types of projects that can be created
 /// TODO: Figure out how to call them like `--binary`, `--static` and so on.
-#[derive(parse_display::Display, clap::ArgEnum, Clone, Copy, PartialEq, Eq)]
+#[derive(
+    parse_display::Display,
+    clap::ArgEnum,
+    serde::Deserialize,
+    serde::Serialize,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
 pub enum ProjectType {
     /// Binary project that generates an executable, creates a layout with a
     /// main.c
     #[display("binary (application)")]
     #[clap(name = "binary")]
+    #[serde(rename = "binary")]
+    #[default]
     Binary,
 
     /// Library project with a entry lib.c that will compile to a
     /// <project_name>.h and a <project_name>.a/lib
     #[display("library (static)")]
     #[clap(name = "static")]
+    #[serde(rename = "static")]
     StaticLib,
 
     /// Library project with a entry lib.c that will compile to a
     /// <project_name>.h and a <project_name>.so/dll
     #[display("library (dynamic)")]
     #[clap(name = "dynamic")]
+    #[serde(rename = "dynamic")]
     DynamicLib,
 
     /// Header only project that will group all the headers into a single one
     #[display("library (header-only)")]
     #[clap(name = "header")]
+    #[serde(rename = "header")]
     HeaderOnly,
 }
 
+/// Languages supported, needed by the `Tool` to select the compiler driver
+#[derive(
+    parse_display::Display,
+    clap::ArgEnum,
+    serde::Deserialize,
+    serde::Serialize,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+)]
+pub enum Language {
+    #[display("c")]
+    #[clap(name = "c")]
+    #[serde(rename = "c")]
+    #[default]
+    C,
+
+    #[display("cpp")]
+    #[clap(name = "cpp")]
+    #[serde(rename = "cpp")]
+    Cpp,
+}
+
 /// Needed by the `Tool` to know which command to output
 #[derive(parse_display::Display, clap::ArgEnum, Clone, Copy, PartialEq, Eq)]
 pub enum BuildType {
@@ -80,16 +668,141 @@ pub enum BuildType {
     Debug,
 }
 
-impl From<BuildType> for PathBuf {
-    fn from(build_type: BuildType) -> PathBuf {
-        match build_type {
-            BuildType::Release => Path::new("target").join("release"),
-            BuildType::Debug => Path::new("target").join("debug"),
+impl BuildType {
+    /// The name of the profile of this `BuildType`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BuildType::Release => "release",
+            BuildType::Debug => "debug",
+        }
+    }
+}
+
+/// The output format of the build
+#[derive(parse_display::Display, clap::ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Status lines and rendered diagnostics for humans
+    #[display("human")]
+    Human,
+
+    /// A JSON object per line on stdout for other tools
+    #[display("json")]
+    Json,
+}
+
+/// The format of the report of `amargo test`
+#[derive(parse_display::Display, clap::ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// JUnit XML, understood by most CI servers
+    #[display("junit")]
+    Junit,
+
+    /// Test Anything Protocol version 13
+    #[display("tap")]
+    Tap,
+
+    /// A JSON array with an object per test
+    #[display("json")]
+    Json,
+}
+
+impl ReportFormat {
+    /// The extension of the files of this format
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ReportFormat::Junit => "xml",
+            ReportFormat::Tap => "tap",
+            ReportFormat::Json => "json",
         }
     }
 }
 
-#[derive(Subcommand, PartialEq, Eq)]
+/// The compilation settings shared by the subcommands that build the project
+#[derive(Args, Clone, PartialEq, Eq)]
+pub(crate) struct JobsArgs {
+    /// Number of parallel compilations, defaults to the available CPUs
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+
+    /// Keep compiling the rest of sources when one of them fails
+    #[clap(long)]
+    pub keep_going: bool,
+}
+
+/// The settings of `amargo test`, the profile is selected with flags as the
+/// positional argument is the filter
+#[derive(Args, Clone, PartialEq, Eq)]
+pub(crate) struct TestArgs {
+    /// Only run the tests whose name contains the filter
+    pub filter: Option<String>,
+
+    /// Build the tests with the `release` profile
+    #[clap(long)]
+    release: bool,
+
+    /// Build the tests with a profile defined on the `Amargo.toml`
+    #[clap(long, conflicts_with = "release")]
+    profile: Option<String>,
+
+    /// Number of tests running at the same time, defaults to the available
+    /// CPUs
+    #[clap(long)]
+    pub test_threads: Option<usize>,
+
+    /// Run every test instead of stopping on the first failure
+    #[clap(long)]
+    pub no_fail_fast: bool,
+
+    /// Write a report of the results, durations and output of the tests
+    #[clap(long, arg_enum)]
+    pub format: Option<ReportFormat>,
+
+    /// Where to write the report, `target/<profile>/test-results.<ext>` by
+    /// default
+    #[clap(long, requires = "format")]
+    pub report: Option<PathBuf>,
+}
+
+impl TestArgs {
+    /// The name of the selected profile, `debug` by default
+    pub fn profile_name(&self) -> &str {
+        match (&self.profile, self.release) {
+            (Some(profile), _) => profile,
+            (None, true) => BuildType::Release.name(),
+            (None, false) => BuildType::Debug.name(),
+        }
+    }
+}
+
+/// The profile selection shared by the subcommands that build the project
+#[derive(Args, Clone, PartialEq, Eq)]
+pub(crate) struct ProfileArgs {
+    /// Build with the `debug` or the `release` profile
+    #[clap(arg_enum)]
+    mode: Option<BuildType>,
+
+    /// Build with a profile defined on the `Amargo.toml`
+    #[clap(long, conflicts_with = "mode")]
+    profile: Option<String>,
+}
+
+impl ProfileArgs {
+    /// The name of the selected profile, `debug` by default
+    pub fn name(&self) -> &str {
+        match (&self.profile, self.mode) {
+            (Some(profile), _) => profile,
+            (None, Some(mode)) => mode.name(),
+            (None, None) => BuildType::Debug.name(),
+        }
+    }
+
+    /// The directory where the profile puts its artifacts
+    pub fn out_dir(&self) -> PathBuf {
+        Path::new("target").join(self.name())
+    }
+}
+
+#[derive(Subcommand, Clone, PartialEq, Eq)]
 pub(crate) enum Command {
     /// Create a new project of a certain type with `project_name`
     New {
@@ -99,20 +812,39 @@ pub(crate) enum Command {
         /// The type of the project
         #[clap(arg_enum, default_value_t=ProjectType::Binary)]
         project_type: ProjectType,
+
+        /// The language of the project
+        #[clap(long, arg_enum, default_value_t=Language::C)]
+        lang: Language,
     },
 
     /// Builds the project if it has benn updated
     #[clap(visible_alias = "b")]
     Build {
-        #[clap(arg_enum, default_value_t=BuildType::Debug)]
-        mode: BuildType,
+        #[clap(flatten)]
+        profile: ProfileArgs,
+
+        #[clap(flatten)]
+        jobs: JobsArgs,
+
+        /// Also build the examples of `examples/`
+        #[clap(long)]
+        examples: bool,
     },
 
     /// Builds the project if it has been updated and runs it (build + run)
     #[clap(visible_alias = "r")]
     Run {
-        #[clap(arg_enum, default_value_t=BuildType::Debug)]
-        mode: BuildType,
+        #[clap(flatten)]
+        profile: ProfileArgs,
+
+        #[clap(flatten)]
+        jobs: JobsArgs,
+
+        /// Run the example of `examples/` with this name instead of the
+        /// project
+        #[clap(long)]
+        example: Option<String>,
 
         /// The arguments provided in the form `-- <exe_args..>` they are
         /// passed as arguments to the target to run (if any)
@@ -120,8 +852,76 @@ pub(crate) enum Command {
         exe_args: Vec<String>,
     },
 
+    /// Builds the programs of `tests/` against the project and runs them
+    #[clap(visible_alias = "t")]
+    Test {
+        #[clap(flatten)]
+        test: TestArgs,
+
+        #[clap(flatten)]
+        jobs: JobsArgs,
+    },
+
+    /// Builds the benchmarks of `benches/` with the `release` profile and
+    /// runs them
+    Bench {
+        /// Only run the benchmarks whose name contains the filter
+        filter: Option<String>,
+
+        /// Number of samples taken of every benchmark
+        #[clap(long, default_value_t = 30)]
+        samples: usize,
+
+        #[clap(flatten)]
+        jobs: JobsArgs,
+    },
+
+    /// Builds the project with the `release` profile and installs its
+    /// executables, libraries and public headers
+    Install {
+        /// Where to install, `AMARGO_HOME` or `~/.amargo` by default
+        #[clap(long)]
+        prefix: Option<PathBuf>,
+
+        #[clap(flatten)]
+        jobs: JobsArgs,
+    },
+
+    /// Removes the files installed by `amargo install`
+    Uninstall {
+        /// The project to uninstall, the current one by default
+        project_name: Option<String>,
+
+        /// Where it was installed, `AMARGO_HOME` or `~/.amargo` by default
+        #[clap(long)]
+        prefix: Option<PathBuf>,
+    },
+
     /// Removes the `target` folder and other intermediate artifacts created
     /// by a compilation
     #[clap(visible_alias = "c")]
     Clean,
 }
+
+impl Command {
+    /// Make the paths given on the cli relative to `dir` (where amargo was
+    /// invoked), as amargo runs from the root of the project
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        match self {
+            Command::Test { test, .. } => {
+                if let Some(report) = &mut test.report {
+                    *report = dir.join(&report);
+                }
+            },
+            Command::Install {
+                prefix: Some(prefix),
+                ..
+            }
+            | Command::Uninstall {
+                prefix: Some(prefix),
+                ..
+            } => *prefix = dir.join(&prefix),
+            _ => {},
+        }
+    }
+}
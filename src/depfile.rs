@@ -0,0 +1,184 @@
+//! Handling of the dependencies discovered by the compiler while compiling a
+//! source, GCC and Clang write them to a Makefile like file (`-MMD -MF`) and
+//! MSVC prints them with `/showIncludes`, both are stored on the Makefile
+//! format next to the object
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::*;
+
+/// Prefix of the lines printed by `cl.exe` with `/showIncludes`
+const SHOW_INCLUDES_PREFIX: &str = "Note: including file:";
+
+/// Extract the dependencies of a Makefile like `data` in the form
+/// `<target>: <dep> <dep> \`
+pub(crate) fn parse(data: &str) -> Vec<PathBuf> {
+    // Join the continuation lines
+    let data = data.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    let mut deps = Vec::new();
+    for line in data.lines() {
+        // The target is separated by ": ", a single ':' can be part of a
+        // Windows path
+        let deps_str = match line.find(": ") {
+            Some(i) => &line[i + 2..],
+            None => continue,
+        };
+
+        // Split the dependencies by whitespace unless it's escaped by '\'
+        let mut dep = String::new();
+        let mut chars = deps_str.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => {
+                    dep.push(' ');
+                    chars.next();
+                },
+                '$' if chars.peek() == Some(&'$') => {
+                    dep.push('$');
+                    chars.next();
+                },
+                c if c.is_whitespace() => {
+                    if !dep.is_empty() {
+                        deps.push(PathBuf::from(std::mem::take(&mut dep)));
+                    }
+                },
+                c => dep.push(c),
+            }
+        }
+        if !dep.is_empty() {
+            deps.push(PathBuf::from(dep));
+        }
+    }
+
+    deps
+}
+
+/// Read and parse the depfile at `path`, `None` if it doesn't exist or can't
+/// be read
+pub(crate) fn read(path: &Path) -> Option<Vec<PathBuf>> {
+    fs::read_to_string(path).ok().map(|data| parse(&data))
+}
+
+/// Write the depfile at `path` of `target` with its `deps`
+pub(crate) fn write(
+    path: &Path,
+    target: &Path,
+    deps: &[PathBuf],
+) -> Result<()> {
+    let escape = |p: &Path| p.display().to_string().replace(' ', "\\ ");
+
+    let mut data = format!("{}:", escape(target));
+    for dep in deps {
+        data.push_str(" \\\n  ");
+        data.push_str(&escape(dep));
+    }
+    data.push('\n');
+
+    fs::write(path, data)
+        .map_err(|e| Error::CannotCreate(path.to_path_buf(), e))
+}
+
+/// Split the output of `cl.exe /showIncludes` into the included files and the
+/// rest of the output
+pub(crate) fn parse_show_includes(output: &str) -> (Vec<PathBuf>, String) {
+    let mut deps = Vec::new();
+    let mut rest = String::new();
+    for line in output.lines() {
+        match line.strip_prefix(SHOW_INCLUDES_PREFIX) {
+            Some(dep) => deps.push(PathBuf::from(dep.trim())),
+            None => {
+                rest.push_str(line);
+                rest.push('\n');
+            },
+        }
+    }
+
+    (deps, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn parse_single_line() {
+        let deps = parse("obj/main.c.o: src/main.c include/lib.h\n");
+        assert_eq!(deps, paths(&["src/main.c", "include/lib.h"]));
+    }
+
+    #[test]
+    fn parse_escaped_spaces() {
+        let deps = parse("main.o: my\\ src/main.c include/a\\ b.h\n");
+        assert_eq!(deps, paths(&["my src/main.c", "include/a b.h"]));
+    }
+
+    #[test]
+    fn parse_dollars() {
+        let deps = parse("main.o: src/$$main.c include/a$$b.h\n");
+        assert_eq!(deps, paths(&["src/$main.c", "include/a$b.h"]));
+    }
+
+    #[test]
+    fn parse_continuations() {
+        let deps = parse("main.o: \\\n  src/main.c \\\n  include/lib.h\n");
+        assert_eq!(deps, paths(&["src/main.c", "include/lib.h"]));
+
+        let deps = parse("main.o: src/main.c \\\r\n  include/lib.h\r\n");
+        assert_eq!(deps, paths(&["src/main.c", "include/lib.h"]));
+    }
+
+    #[test]
+    fn parse_windows_drive_letters() {
+        let deps = parse(
+            "C:\\project\\main.obj: C:\\project\\src\\main.c \\\n  \
+             D:\\sdk\\include\\lib.h\n",
+        );
+        assert_eq!(
+            deps,
+            paths(&["C:\\project\\src\\main.c", "D:\\sdk\\include\\lib.h"])
+        );
+    }
+
+    #[test]
+    fn parse_ignores_lines_without_target() {
+        assert!(parse("\n# comment\n").is_empty());
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        // Unique to this process so concurrent test runs don't share it
+        let dir = std::env::temp_dir()
+            .join(format!("amargo-depfile-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.o.d");
+        let deps = paths(&["my src/main.c", "include/lib.h"]);
+
+        write(&path, Path::new("main.o"), &deps).unwrap();
+        assert_eq!(read(&path), Some(deps));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_show_includes_output() {
+        let output = "main.c\n\
+                      Note: including file: C:\\project\\include\\lib.h\n\
+                      Note: including file:  C:\\sdk\\include\\stdio.h\n\
+                      main.c(3): warning C4013: 'f' undefined\n";
+        let (deps, rest) = parse_show_includes(output);
+
+        assert_eq!(
+            deps,
+            paths(&["C:\\project\\include\\lib.h", "C:\\sdk\\include\\stdio.h"])
+        );
+        assert_eq!(rest, "main.c\nmain.c(3): warning C4013: 'f' undefined\n");
+    }
+}
@@ -0,0 +1,387 @@
+//! Parsing and rendering of the diagnostics (errors, warnings and notes)
+//! printed by the compilers, so they look the same whatever the compiler is
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use console::{style, Style};
+use regex::Regex;
+
+/// How serious a diagnostic is
+#[derive(
+    parse_display::Display, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    #[display("error")]
+    Error,
+
+    #[display("warning")]
+    Warning,
+
+    #[display("note")]
+    Note,
+}
+
+impl Severity {
+    /// Get the severity of the name used by the compilers
+    fn from_name(name: &str) -> Self {
+        match name {
+            "warning" => Severity::Warning,
+            "note" => Severity::Note,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// A diagnostic reported by the compiler
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct Diagnostic {
+    /// The file the diagnostic refers to (or the tool that reported it when
+    /// the diagnostic has no location)
+    pub file: PathBuf,
+
+    /// Line of the diagnostic (starting at 1)
+    pub line: Option<usize>,
+
+    /// Column of the diagnostic (starting at 1)
+    pub column: Option<usize>,
+
+    pub severity: Severity,
+
+    pub message: String,
+
+    /// The notes that follow the diagnostic
+    pub notes: Vec<Diagnostic>,
+}
+
+/// Parse the diagnostics of the `output` of a GCC, Clang or MSVC compiler,
+/// the lines that aren't diagnostics (like the source snippets) are ignored
+pub(crate) fn parse(output: &str) -> Vec<Diagnostic> {
+    // `<file>:<line>:<column>: <severity>: <message>` for GCC and Clang
+    let gnu_re = Regex::new(
+        r"^(?P<file>.+?):(?P<line>\d+):(?:(?P<column>\d+):)? (?P<severity>fatal error|error|warning|note): (?P<message>.*)$",
+    )
+    .unwrap();
+
+    // `<tool>: <severity>: <message>` for GCC and Clang without location
+    let gnu_tool_re = Regex::new(
+        r"^(?P<file>[^\s:]+): (?P<severity>fatal error|error|warning|note): (?P<message>.*)$",
+    )
+    .unwrap();
+
+    // `<file>(<line>,<column>): <severity> <code>: <message>` for MSVC
+    let msvc_re = Regex::new(
+        r"^(?P<file>.+?)\((?P<line>\d+)(?:,(?P<column>\d+))?\)\s*: (?P<severity>fatal error|error|warning|note)(?: (?P<code>[A-Z]+\d+))?\s*: (?P<message>.*)$",
+    )
+    .unwrap();
+
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim_end();
+        let caps = match gnu_re
+            .captures(line)
+            .or_else(|| msvc_re.captures(line))
+            .or_else(|| gnu_tool_re.captures(line))
+        {
+            Some(caps) => caps,
+            None => continue,
+        };
+
+        let number =
+            |name| caps.name(name).and_then(|m| m.as_str().parse().ok());
+        let message = match caps.name("code") {
+            Some(code) => format!("{} [{}]", &caps["message"], code.as_str()),
+            None => caps["message"].to_string(),
+        };
+        let diagnostic = Diagnostic {
+            file: PathBuf::from(&caps["file"]),
+            line: number("line"),
+            column: number("column"),
+            severity: Severity::from_name(&caps["severity"]),
+            message,
+            notes: Vec::new(),
+        };
+
+        // The notes belong to the previous diagnostic
+        match diagnostics.last_mut() {
+            Some(last) if diagnostic.severity == Severity::Note => {
+                last.notes.push(diagnostic)
+            },
+            _ => diagnostics.push(diagnostic),
+        }
+    }
+
+    diagnostics
+}
+
+impl Diagnostic {
+    /// The location in the form `<file>:<line>:<column>` with the file
+    /// relative to `base` if possible
+    fn location(&self, base: &Path) -> String {
+        let mut location = self
+            .file
+            .strip_prefix(base)
+            .unwrap_or(&self.file)
+            .display()
+            .to_string();
+        if let Some(line) = self.line {
+            location.push_str(&format!(":{}", line));
+            if let Some(column) = self.column {
+                location.push_str(&format!(":{}", column));
+            }
+        }
+
+        location
+    }
+
+    /// Render the diagnostic with the paths relative to `base`, like
+    ///
+    /// ```text
+    /// error: expected ';' before '}' token
+    ///   --> src/main.c:4:14
+    ///    |
+    ///  4 |     return 0
+    ///    |              ^
+    ///    = note: ...
+    /// ```
+    pub fn render(&self, base: &Path) -> String {
+        let severity_style = match self.severity {
+            Severity::Error => Style::new().red().bold(),
+            Severity::Warning => Style::new().yellow().bold(),
+            Severity::Note => Style::new().cyan().bold(),
+        };
+        let mut rendered = format!(
+            "{}{}\n",
+            severity_style.apply_to(self.severity),
+            style(format!(": {}", self.message)).bold()
+        );
+
+        // Diagnostics without line are not related to a source
+        let line = match self.line {
+            Some(line) => line,
+            None => return rendered,
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        rendered.push_str(&format!(
+            "{}{} {}\n",
+            gutter,
+            style("-->").blue().bold(),
+            self.location(base)
+        ));
+
+        // Show the line of the source and point to the column
+        let code = fs::read_to_string(&self.file).ok().and_then(|data| {
+            data.lines().nth(line.checked_sub(1)?).map(String::from)
+        });
+        if let Some(code) = code {
+            let bar = style("|").blue().bold();
+            rendered.push_str(&format!("{} {}\n", gutter, bar));
+            rendered.push_str(&format!(
+                "{} {} {}\n",
+                style(line).blue().bold(),
+                bar,
+                code
+            ));
+            if let Some(column) = self.column {
+                // Keep the tabs so the caret is aligned
+                let padding = code
+                    .chars()
+                    .take(column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                rendered.push_str(&format!(
+                    "{} {} {}{}\n",
+                    gutter,
+                    bar,
+                    padding,
+                    severity_style.apply_to("^")
+                ));
+            }
+        }
+
+        for note in &self.notes {
+            rendered.push_str(&format!(
+                "{} {} {}: {} ({})\n",
+                gutter,
+                style("=").blue().bold(),
+                style("note").bold(),
+                note.message,
+                note.location(base)
+            ));
+        }
+
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        file: &str,
+        line: Option<usize>,
+        column: Option<usize>,
+        severity: Severity,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic {
+            file: PathBuf::from(file),
+            line,
+            column,
+            severity,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_gnu_with_column() {
+        let output = "src/main.c:4:14: error: expected ';' before '}' token\n\
+                      \x20   4 |     return 0\n\
+                      \x20     |             ^\n";
+        assert_eq!(
+            parse(output),
+            vec![diagnostic(
+                "src/main.c",
+                Some(4),
+                Some(14),
+                Severity::Error,
+                "expected ';' before '}' token"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_gnu_without_column() {
+        let output = "src/main.c:7: warning: unused variable 'x'\n";
+        assert_eq!(
+            parse(output),
+            vec![diagnostic(
+                "src/main.c",
+                Some(7),
+                None,
+                Severity::Warning,
+                "unused variable 'x'"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_gnu_fatal_error() {
+        let output = "src/main.c:1:10: fatal error: lib.h: No such file or \
+                      directory\n";
+        assert_eq!(
+            parse(output),
+            vec![diagnostic(
+                "src/main.c",
+                Some(1),
+                Some(10),
+                Severity::Error,
+                "lib.h: No such file or directory"
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_tool_without_location() {
+        let output = "gcc: error: src/missing.c: No such file or directory\n\
+                      cc1: warning: command-line option is not valid\n";
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic(
+                    "gcc",
+                    None,
+                    None,
+                    Severity::Error,
+                    "src/missing.c: No such file or directory"
+                ),
+                diagnostic(
+                    "cc1",
+                    None,
+                    None,
+                    Severity::Warning,
+                    "command-line option is not valid"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_msvc() {
+        let output = "main.c\n\
+                      C:\\project\\src\\main.c(4,14): error C2143: syntax \
+                      error: missing ';' before '}'\n\
+                      C:\\project\\src\\main.c(7): warning C4101: 'x': \
+                      unreferenced local variable\n";
+        assert_eq!(
+            parse(output),
+            vec![
+                diagnostic(
+                    "C:\\project\\src\\main.c",
+                    Some(4),
+                    Some(14),
+                    Severity::Error,
+                    "syntax error: missing ';' before '}' [C2143]"
+                ),
+                diagnostic(
+                    "C:\\project\\src\\main.c",
+                    Some(7),
+                    None,
+                    Severity::Warning,
+                    "'x': unreferenced local variable [C4101]"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_notes_attach_to_previous() {
+        let output = "src/main.c:5:5: error: conflicting types for 'add'\n\
+                      include/lib.h:3:5: note: previous declaration of \
+                      'add'\n\
+                      src/main.c:9:1: warning: control reaches end\n";
+        let mut error = diagnostic(
+            "src/main.c",
+            Some(5),
+            Some(5),
+            Severity::Error,
+            "conflicting types for 'add'",
+        );
+        error.notes.push(diagnostic(
+            "include/lib.h",
+            Some(3),
+            Some(5),
+            Severity::Note,
+            "previous declaration of 'add'",
+        ));
+        let warning = diagnostic(
+            "src/main.c",
+            Some(9),
+            Some(1),
+            Severity::Warning,
+            "control reaches end",
+        );
+
+        assert_eq!(parse(output), vec![error, warning]);
+    }
+
+    #[test]
+    fn parse_note_without_previous() {
+        let output = "src/main.c:2:1: note: in file included from here\n";
+        assert_eq!(
+            parse(output),
+            vec![diagnostic(
+                "src/main.c",
+                Some(2),
+                Some(1),
+                Severity::Note,
+                "in file included from here"
+            )]
+        );
+    }
+}
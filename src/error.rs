@@ -1,9 +1,11 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf, process::ExitStatus};
+
+use crate::config::Language;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-/// Error type used in the program
-/// TODO: Use some macro crate so the displayed message on panic is better
+/// Error type used in the program, `main` prints its message (and hint, if
+/// any) and exits with its `exit_code`
 #[derive(Debug)]
 #[allow(unused)]
 pub enum Error {
@@ -16,6 +18,9 @@ pub enum Error {
     /// Invalid new project path
     InvalidProjectPath(PathBuf),
 
+    /// `amargo new` found a project already on the path
+    ProjectExists(PathBuf),
+
     /// Impossible to create an object (also used in case is impossible to
     /// create and then write)
     CannotCreate(PathBuf, std::io::Error),
@@ -45,21 +50,283 @@ pub enum Error {
     /// couldn't be resolved
     MissingIncludes(PathBuf, Vec<String>),
 
-    /// Error while compilating
+    /// Error while compilating, contains the sources that failed
     ///
-    /// TODO: For the moment this contains nothing, but in the future I'd like
-    /// the tool to have a check subcommand like cargo that statically checks
-    Compilation,
+    /// TODO: In the future I'd like the tool to have a check subcommand like
+    /// cargo that statically checks
+    Compilation(Vec<PathBuf>),
 
-    /// Couldn't find a default compiler
+    /// Couldn't find a default compiler for the language
     ///
     /// TODO: In the future this might have an associated `PathBuf` because it
     /// can be a custom compiler path what couldn't be found
-    NoCompilerFound,
+    NoCompilerFound(Language),
+
+    /// The selected profile is not defined on the `Amargo.toml`
+    UnknownProfile(String),
+
+    /// The profile is defined but can't be used (profile name, reason)
+    InvalidProfile(String, String),
+
+    /// The language standard is unknown or the compiler doesn't support it
+    /// (standard, compiler path)
+    UnsupportedStandard(String, PathBuf),
+
+    /// Couldn't find an archiver to create static libraries
+    NoArchiverFound,
+
+    /// A target couldn't be linked with the command line
+    CannotLink(PathBuf, Vec<String>),
+
+    /// There is no example with this name on `examples/`
+    UnknownExample(String),
+
+    /// Some tests of `amargo test` failed, contains how many
+    TestsFailed(usize),
+
+    /// Some benchmarks of `amargo bench` failed, contains how many
+    BenchesFailed(usize),
+
+    /// There is no `--prefix` and no home directory to install to
+    NoInstallPrefix,
+
+    /// The project isn't installed on the prefix (project name, prefix)
+    NotInstalled(String, PathBuf),
 
-    /// Project couldn't be linked (provide an explanation)
+    /// The `--manifest-path` doesn't point to a file
+    ManifestNotFound(PathBuf),
+
+    /// The `Amargo.toml` can't be parsed or has invalid values (path, error)
+    InvalidManifest(PathBuf, String),
+
+    /// The name given to `amargo new` can't be used (name, reason)
+    InvalidProjectName(String, String),
+
+    /// A dependency of `[dependencies]` can't be built (name, reason)
+    InvalidDependency(String, String),
+
+    /// The program run by `amargo run` didn't exit successfully
+    ProgramFailed(PathBuf, ExitStatus),
+}
+
+impl Error {
+    /// The exit code of `amargo` when it fails with this error, grouped by
+    /// category:
     ///
-    /// TODO: Instead of a String use a new Error type only for linking errors,
-    /// this should be done when output parsing is avaible
-    CannotLink(String),
+    /// * 1: the project couldn't be built
+    /// * 2: the project or the command line are not valid
+    /// * 3: a tool is missing or couldn't be run
+    /// * 4: a file or directory couldn't be accessed
+    /// * 101: some tests or benchmarks failed
+    ///
+    /// The failures of `amargo run` exit with the code of the program
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Compilation(_)
+            | Error::MissingIncludes(..)
+            | Error::CannotLink(..) => 1,
+            Error::InvalidProjectPath(_)
+            | Error::ProjectExists(_)
+            | Error::NotAProject(_)
+            | Error::ManifestNotFound(_)
+            | Error::InvalidManifest(..)
+            | Error::InvalidProjectName(..)
+            | Error::InvalidDependency(..)
+            | Error::UnknownProfile(_)
+            | Error::InvalidProfile(..)
+            | Error::UnsupportedStandard(..)
+            | Error::UnknownExample(_)
+            | Error::NoInstallPrefix
+            | Error::NotInstalled(..) => 2,
+            Error::NoCompilerFound(_)
+            | Error::NoArchiverFound
+            | Error::ProcessExec(_)
+            | Error::ProcessCreation(..) => 3,
+            Error::DirNotExist(_)
+            | Error::CurrentDirInvalid(..)
+            | Error::CannotCreate(..)
+            | Error::CannotRemove(..)
+            | Error::CannotRead(..)
+            | Error::FileListing(_) => 4,
+            Error::TestsFailed(_) | Error::BenchesFailed(_) => 101,
+            Error::ProgramFailed(_, status) => status.code().unwrap_or(1),
+        }
+    }
+
+    /// A suggestion of how to solve the error (if any)
+    pub fn hint(&self) -> Option<String> {
+        let hint = match self {
+            Error::NotAProject(_) => {
+                "create one with `amargo new <name>` or point to its \
+                 `Amargo.toml` with `--manifest-path`"
+                    .into()
+            },
+            Error::ProjectExists(_) => {
+                "choose another name or build the existing project".into()
+            },
+            Error::MissingIncludes(..) => {
+                "the project headers are searched on `include/`".into()
+            },
+            Error::NoCompilerFound(Language::C) => {
+                "install clang or gcc (or MSVC on Windows) and make sure it's \
+                 on the PATH"
+                    .into()
+            },
+            Error::NoCompilerFound(Language::Cpp) => {
+                "install clang++ or g++ (or MSVC on Windows) and make sure \
+                 it's on the PATH"
+                    .into()
+            },
+            Error::NoArchiverFound => {
+                "install `ar` (binutils), `llvm-ar` or `lib.exe` on Windows"
+                    .into()
+            },
+            Error::UnknownProfile(name) => format!(
+                "define it on the `Amargo.toml` as `[profile.{}]` with \
+                 `inherits = \"debug\"` or `inherits = \"release\"`",
+                name
+            ),
+            Error::UnknownExample(_) => {
+                "the examples are the sources of `examples/`".into()
+            },
+            Error::NoInstallPrefix => {
+                "pass `--prefix` or set the `AMARGO_HOME` variable".into()
+            },
+            Error::NotInstalled(..) => {
+                "install it with `amargo install`, or pass the `--prefix` it \
+                 was installed on"
+                    .into()
+            },
+            Error::TestsFailed(_) => {
+                "run `amargo test <filter>` to only run some of them".into()
+            },
+            _ => return None,
+        };
+
+        Some(hint)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DirNotExist(path) => {
+                write!(f, "directory `{}` does not exist", path.display())
+            },
+            Error::CurrentDirInvalid(path, e) => write!(
+                f,
+                "invalid current directory `{}`: {}",
+                path.display(),
+                e
+            ),
+            Error::InvalidProjectPath(path) => {
+                write!(f, "invalid project path `{}`", path.display())
+            },
+            Error::ProjectExists(path) => {
+                write!(f, "a project already exists at `{}`", path.display())
+            },
+            Error::CannotCreate(path, e) => {
+                write!(f, "cannot create `{}`: {}", path.display(), e)
+            },
+            Error::CannotRemove(path, e) => {
+                write!(f, "cannot remove `{}`: {}", path.display(), e)
+            },
+            Error::NotAProject(path) => write!(
+                f,
+                "could not find `Amargo.toml` in `{}` or any parent directory",
+                path.display()
+            ),
+            Error::CannotRead(path, e) => {
+                write!(f, "cannot read `{}`: {}", path.display(), e)
+            },
+            Error::FileListing(e) => write!(f, "cannot list the files: {}", e),
+            Error::ProcessExec(e) => write!(f, "cannot run a command: {}", e),
+            Error::ProcessCreation(path, e) => {
+                write!(f, "could not execute `{}`: {}", path.display(), e)
+            },
+            Error::MissingIncludes(path, includes) => write!(
+                f,
+                "cannot find the includes of `{}`: {}",
+                path.display(),
+                includes.join(", ")
+            ),
+            Error::Compilation(sources) => write!(
+                f,
+                "could not compile {} source{}: {}",
+                sources.len(),
+                if sources.len() == 1 { "" } else { "s" },
+                sources
+                    .iter()
+                    .map(|s| format!("`{}`", s.display()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Error::NoCompilerFound(Language::C) => {
+                write!(f, "no C compiler found")
+            },
+            Error::NoCompilerFound(Language::Cpp) => {
+                write!(f, "no C++ compiler found")
+            },
+            Error::UnknownProfile(name) => {
+                write!(f, "profile `{}` is not defined", name)
+            },
+            Error::InvalidProfile(name, reason) => {
+                write!(f, "invalid profile `{}`: {}", name, reason)
+            },
+            Error::UnsupportedStandard(standard, path) => write!(
+                f,
+                "the language standard `{}` is not supported by `{}`",
+                standard,
+                path.display()
+            ),
+            Error::NoArchiverFound => {
+                write!(f, "no archiver found to create static libraries")
+            },
+            Error::CannotLink(target, command) => write!(
+                f,
+                "could not link `{}` with `{}`",
+                target.display(),
+                command.join(" ")
+            ),
+            Error::UnknownExample(name) => {
+                write!(f, "no example named `{}`", name)
+            },
+            Error::TestsFailed(1) => write!(f, "1 test failed"),
+            Error::TestsFailed(failed) => write!(f, "{} tests failed", failed),
+            Error::BenchesFailed(1) => write!(f, "1 benchmark failed"),
+            Error::BenchesFailed(failed) => {
+                write!(f, "{} benchmarks failed", failed)
+            },
+            Error::NoInstallPrefix => {
+                write!(f, "no home directory found to install to")
+            },
+            Error::NotInstalled(name, prefix) => write!(
+                f,
+                "`{}` is not installed on `{}`",
+                name,
+                prefix.display()
+            ),
+            Error::ManifestNotFound(path) => {
+                write!(f, "manifest `{}` does not exist", path.display())
+            },
+            Error::InvalidManifest(path, message) => write!(
+                f,
+                "invalid manifest `{}`: {}",
+                path.display(),
+                message
+            ),
+            Error::InvalidProjectName(name, reason) => {
+                write!(f, "invalid project name `{}`: {}", name, reason)
+            },
+            Error::InvalidDependency(name, reason) => {
+                write!(f, "invalid dependency `{}`: {}", name, reason)
+            },
+            Error::ProgramFailed(path, status) => write!(
+                f,
+                "process didn't exit successfully: `{}` ({})",
+                path.display(),
+                status
+            ),
+        }
+    }
 }
@@ -0,0 +1,195 @@
+//! The install prefix of `amargo install`, the executables go to `bin`, the
+//! libraries to `lib` and the public headers to `include/<name>`, every
+//! install records the files it created on a manifest at `share/amargo` so
+//! `amargo uninstall` removes exactly those
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Project, ProjectType},
+    error::*,
+};
+
+use console::style;
+use walkdir::WalkDir;
+
+/// Directory of the manifests inside the prefix
+const MANIFEST_DIR: &str = "share/amargo";
+
+/// The files installed of a project
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+struct InstallManifest {
+    /// The version of the project installed
+    version: String,
+
+    /// The files installed relative to the prefix
+    files: Vec<PathBuf>,
+}
+
+impl InstallManifest {
+    /// The path of the manifest of the project `name` on `prefix`
+    fn path(prefix: &Path, name: &str) -> PathBuf {
+        prefix.join(MANIFEST_DIR).join(name).with_extension("toml")
+    }
+
+    /// Load the manifest of the project `name`, if it was installed
+    fn load(prefix: &Path, name: &str) -> Option<Self> {
+        fs::read(Self::path(prefix, name))
+            .ok()
+            .and_then(|data| toml::from_slice(&data[..]).ok())
+    }
+
+    /// Store the manifest of the project `name`
+    fn save(&self, prefix: &Path, name: &str) -> Result<()> {
+        let path = Self::path(prefix, name);
+        create_parent(&path)?;
+        let data = toml::to_string(self).unwrap();
+
+        fs::write(&path, data).map_err(|e| Error::CannotCreate(path, e))
+    }
+}
+
+/// The prefix used when none is given, `AMARGO_HOME` or `~/.amargo`
+pub(crate) fn default_prefix() -> Result<PathBuf> {
+    if let Some(home) = std::env::var_os("AMARGO_HOME") {
+        return Ok(PathBuf::from(home));
+    }
+
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".amargo"))
+        .ok_or(Error::NoInstallPrefix)
+}
+
+/// Create the parent directory of `path` if it doesn't exist
+fn create_parent(path: &Path) -> Result<()> {
+    let parent = path.parent().unwrap();
+    fs::create_dir_all(parent)
+        .map_err(|e| Error::CannotCreate(parent.to_path_buf(), e))
+}
+
+/// Copy `from` to `to` replacing it, the symlinks are copied as symlinks
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    create_parent(to)?;
+    if fs::symlink_metadata(to).is_ok() {
+        fs::remove_file(to).map_err(|e| Error::CannotRemove(to.into(), e))?;
+    }
+
+    #[cfg(unix)]
+    if let Ok(link) = fs::read_link(from) {
+        return std::os::unix::fs::symlink(link, to)
+            .map_err(|e| Error::CannotCreate(to.into(), e));
+    }
+
+    fs::copy(from, to)
+        .map(|_| ())
+        .map_err(|e| Error::CannotCreate(to.into(), e))
+}
+
+/// Where an `artifact` of the project goes inside the prefix
+fn artifact_dir(project: &Project, artifact: &Path) -> PathBuf {
+    let is_dll = artifact.extension().is_some_and(|e| e == "dll");
+    match project.kind {
+        ProjectType::Binary => PathBuf::from("bin"),
+        ProjectType::DynamicLib if is_dll => PathBuf::from("bin"),
+        ProjectType::StaticLib | ProjectType::DynamicLib => {
+            PathBuf::from("lib")
+        },
+        ProjectType::HeaderOnly => Path::new("include").join(&project.name),
+    }
+}
+
+/// Install the `artifacts` of the `project` and (for libraries) the
+/// headers of `include_dir` on `prefix`, the files of a previous install
+/// are removed first
+pub(crate) fn install(
+    project: &Project,
+    artifacts: &[PathBuf],
+    include_dir: &Path,
+    prefix: &Path,
+) -> Result<()> {
+    if InstallManifest::load(prefix, &project.name).is_some() {
+        uninstall(&project.name, prefix)?;
+    }
+
+    let mut files = Vec::new();
+    for artifact in artifacts {
+        let file = artifact_dir(project, artifact)
+            .join(artifact.file_name().unwrap());
+        copy(artifact, &prefix.join(&file))?;
+        files.push(file);
+    }
+
+    // The public headers of the libraries, header-only projects install
+    // their single header instead
+    let is_lib = matches!(
+        project.kind,
+        ProjectType::StaticLib | ProjectType::DynamicLib
+    );
+    if is_lib && include_dir.is_dir() {
+        for entry in WalkDir::new(include_dir).sort_by_file_name() {
+            let entry = entry.map_err(Error::FileListing)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path =
+                entry.path().strip_prefix(include_dir).unwrap();
+            let file = Path::new("include")
+                .join(&project.name)
+                .join(relative_path);
+            copy(entry.path(), &prefix.join(&file))?;
+            files.push(file);
+        }
+    }
+
+    for file in &files {
+        println!(
+            "{:>12} {}",
+            style("Installing").cyan(),
+            prefix.join(file).display()
+        );
+    }
+
+    InstallManifest {
+        version: project.version.clone(),
+        files,
+    }
+    .save(prefix, &project.name)
+}
+
+/// Remove the files installed of the project `name` from `prefix` and the
+/// directories of its headers that are left empty
+pub(crate) fn uninstall(name: &str, prefix: &Path) -> Result<()> {
+    // The name is part of the path of the manifest, it can't leave the prefix
+    Project::validate_name(name)
+        .map_err(|reason| Error::InvalidProjectName(name.into(), reason))?;
+
+    let manifest = InstallManifest::load(prefix, name)
+        .ok_or_else(|| Error::NotInstalled(name.to_string(), prefix.into()))?;
+    let headers_dir = prefix.join("include").join(name);
+
+    for file in &manifest.files {
+        let path = prefix.join(file);
+        if fs::symlink_metadata(&path).is_ok() {
+            fs::remove_file(&path)
+                .map_err(|e| Error::CannotRemove(path.clone(), e))?;
+            println!("{:>12} {}", style("Removing").cyan(), path.display());
+        }
+
+        // `remove_dir` only removes empty directories
+        let mut dir = path.parent();
+        while let Some(parent) = dir.filter(|d| d.starts_with(&headers_dir)) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+
+    let path = InstallManifest::path(prefix, name);
+    fs::remove_file(&path).map_err(|e| Error::CannotRemove(path, e))
+}
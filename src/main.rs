@@ -5,17 +5,30 @@ use std::{
     time::Instant,
 };
 
+mod bench;
 mod build;
+mod compdb;
 mod config;
+mod depfile;
+mod diagnostic;
 mod error;
+mod install;
+mod message;
+mod pool;
+mod report;
+mod state;
+mod test;
 mod tool;
 
 use crate::{
-    build::Build,
+    build::{Build, Examples},
     config::{
-        BuildType, Cli, Command, Config, Project, ProjectConfig, ProjectType,
+        BuildType, Cli, Command, Config, JobsArgs, Language, MessageFormat,
+        ProfileArgs, Project, ProjectConfig, ProjectType, TestArgs,
     },
     error::{Error, Result},
+    message::Message,
+    test::{RunOptions, TestOutcome, TestProgram},
 };
 
 use clap::Parser;
@@ -28,6 +41,8 @@ const C_BINARY_TEMPLATE: Dir = include_dir!("./templates/c/binary");
 const CPP_BINARY_TEMPLATE: Dir = include_dir!("./templates/cpp/binary");
 const C_LIBRARY_TEMPLATE: Dir = include_dir!("./templates/c/library");
 const CPP_LIBRARY_TEMPLATE: Dir = include_dir!("./templates/cpp/library");
+const C_HEADER_TEMPLATE: Dir = include_dir!("./templates/c/header");
+const CPP_HEADER_TEMPLATE: Dir = include_dir!("./templates/cpp/header");
 
 // The extension of the executable is platform dependent
 #[cfg(target_os = "windows")]
@@ -42,24 +57,31 @@ fn create_project(
     config: &ProjectConfig,
     project_type: ProjectType,
 ) -> Result<()> {
-    let project_config = config.config.as_ref().unwrap();
+    let project_config = config.manifest()?;
     let project_name = &project_config.project.name;
     let project_path = config.working_dir.join(project_name);
 
     // Check if the project already exists
     if project_path.join("Amargo.toml").is_file() {
-        println!("Project with name {} already exists", project_name);
-        std::process::exit(0);
+        return Err(Error::ProjectExists(project_path));
     }
 
-    // Extract the project template on `project_path`
-    match project_type {
-        ProjectType::Binary => C_BINARY_TEMPLATE.extract(&project_path),
-        ProjectType::StaticLib 
-            | ProjectType::DynamicLib => C_LIBRARY_TEMPLATE.extract(&project_path), 
-        p => todo!("Project type {} not implemented yet", p),
-    }
-    .map_err(|e| Error::CannotCreate(project_path.clone(), e))?;
+    // Extract the project template of the language on `project_path`
+    let template = match (project_type, project_config.project.language) {
+        (ProjectType::Binary, Language::C) => &C_BINARY_TEMPLATE,
+        (ProjectType::Binary, Language::Cpp) => &CPP_BINARY_TEMPLATE,
+        (ProjectType::StaticLib | ProjectType::DynamicLib, Language::C) => {
+            &C_LIBRARY_TEMPLATE
+        },
+        (ProjectType::StaticLib | ProjectType::DynamicLib, Language::Cpp) => {
+            &CPP_LIBRARY_TEMPLATE
+        },
+        (ProjectType::HeaderOnly, Language::C) => &C_HEADER_TEMPLATE,
+        (ProjectType::HeaderOnly, Language::Cpp) => &CPP_HEADER_TEMPLATE,
+    };
+    template
+        .extract(&project_path)
+        .map_err(|e| Error::CannotCreate(project_path.clone(), e))?;
 
     // Write the `Amargo.toml` from the already generated config
     let toml_path = project_path.join("Amargo.toml");
@@ -78,37 +100,287 @@ fn create_project(
     Ok(())
 }
 
-/// Builds the binary of a project given a configuration and a build type
-fn build_project(config: &ProjectConfig, mode: BuildType) -> Result<bool> {
-    // Compile and link the project given the mode
-    Build::new(config, mode)?
+/// The build of the project in `profile` with the compilation settings,
+/// its headers and its dependencies (already built)
+fn prepare_build<'a>(
+    config: &'a ProjectConfig,
+    profile: &str,
+    jobs: &JobsArgs,
+) -> Result<Build<'a>> {
+    let mut build = Build::new(config, profile)?;
+    build
+        .jobs(config.jobs(jobs.jobs))
+        .keep_going(jobs.keep_going)
         .include("include")?
-        .files("src")?
-        .compile()?
-        .link()
+        .dependencies()?;
+
+    Ok(build)
+}
+
+/// Print that the build of the project in `profile` has finished, followed
+/// by the `detail` of how it went (if any)
+fn print_finished(
+    config: &ProjectConfig,
+    profile: &str,
+    detail: &str,
+) -> Result<()> {
+    println!(
+        "{:>12} {} {} [{}]{}",
+        style("Finished").cyan(),
+        config.manifest()?.project.name,
+        profile,
+        config.profile(profile)?.summary(),
+        detail
+    );
+
+    Ok(())
+}
+
+/// Builds the binary of a project given a configuration, a profile and the
+/// compilation settings, and then the selected `examples` (if any)
+fn build_project(
+    config: &ProjectConfig,
+    profile: &str,
+    jobs: &JobsArgs,
+    examples: Option<Examples>,
+) -> Result<bool> {
+    let mut build = prepare_build(config, profile, jobs)?;
+
+    // Header only projects just group their headers into a single one,
+    // the rest are compiled and linked given the profile
+    let kind = config.manifest()?.project.kind;
+    let mut changes = if kind == ProjectType::HeaderOnly {
+        build.amalgamate()?
+    } else {
+        build.files("src")?.compile()?.link()?
+    };
+
+    if let Some(examples) = examples {
+        changes |= build.examples("examples", examples)?;
+    }
+
+    Ok(changes)
+}
+
+/// Build the project and its tests
+fn build_tests(
+    config: &ProjectConfig,
+    profile: &str,
+    jobs: &JobsArgs,
+) -> Result<Vec<TestProgram>> {
+    let mut build = prepare_build(config, profile, jobs)?;
+    if config.manifest()?.project.kind != ProjectType::HeaderOnly {
+        build.files("src")?.compile()?;
+    }
+
+    build.tests("tests")
+}
+
+/// Build the project and its tests, then run them, with JSON messages the
+/// end of the build and the result of every test are emitted instead
+fn test_project(
+    config: &ProjectConfig,
+    test_args: &TestArgs,
+    jobs: &JobsArgs,
+) -> Result<()> {
+    let project_name = &config.manifest()?.project.name;
+    let profile = test_args.profile_name();
+    let human = config.cli.message_format == MessageFormat::Human;
+
+    let programs = match human {
+        true => {
+            println!("{:>12} {:?}", style("Compiling").cyan(), project_name);
+            let programs = build_tests(config, profile, jobs)?;
+            print_finished(config, profile, "")?;
+            programs
+        },
+        false => {
+            let programs = build_tests(config, profile, jobs);
+            Message::BuildFinished {
+                success: programs.is_ok(),
+            }
+            .emit();
+            programs?
+        },
+    };
+
+    let options = RunOptions {
+        filter: test_args.filter.as_deref(),
+        threads: test_args.test_threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |n| n.get())
+        }),
+        fail_fast: !test_args.no_fail_fast,
+    };
+    let results = test::run(config, &programs, &options)?;
+
+    // Write the report even if some tests failed, that's when it matters
+    if let Some(format) = test_args.format {
+        let path = test_args.report.clone().unwrap_or_else(|| {
+            Path::new("target")
+                .join(profile)
+                .join("test-results")
+                .with_extension(format.extension())
+        });
+        report::write(&path, format, project_name, &results)?;
+        if human {
+            println!("{:>12} {}", style("Report").cyan(), path.display());
+        }
+    }
+
+    match results
+        .iter()
+        .filter(|r| r.outcome != TestOutcome::Passed)
+        .count()
+    {
+        0 => Ok(()),
+        failed => Err(Error::TestsFailed(failed)),
+    }
+}
+
+/// Build the project and its benchmarks in release, then run them
+fn bench_project(
+    config: &ProjectConfig,
+    filter: Option<&str>,
+    samples: usize,
+    jobs: &JobsArgs,
+) -> Result<()> {
+    let project_name = &config.manifest()?.project.name;
+    let profile = BuildType::Release.name();
+    println!("{:>12} {:?}", style("Compiling").cyan(), project_name);
+
+    let mut build = prepare_build(config, profile, jobs)?;
+    if config.manifest()?.project.kind != ProjectType::HeaderOnly {
+        build.files("src")?.compile()?;
+    }
+    let programs = build.benches("benches")?;
+    print_finished(config, profile, "")?;
+
+    let dir = Path::new("target").join("bench");
+    match bench::run(&programs, filter, samples, &dir)? {
+        0 => Ok(()),
+        failed => Err(Error::BenchesFailed(failed)),
+    }
+}
+
+/// Build the project in release and install it on `prefix`
+fn install_project(
+    config: &ProjectConfig,
+    prefix: &Path,
+    jobs: &JobsArgs,
+) -> Result<()> {
+    let project = &config.manifest()?.project;
+    let profile = BuildType::Release.name();
+    println!("{:>12} {:?}", style("Compiling").cyan(), project.name);
+
+    let mut build = prepare_build(config, profile, jobs)?;
+    if project.kind == ProjectType::HeaderOnly {
+        build.amalgamate()?;
+    } else {
+        build.files("src")?.compile()?.link()?;
+    }
+    print_finished(config, profile, "")?;
+
+    install::install(
+        project,
+        &build.artifacts(),
+        &config.working_dir.join("include"),
+        prefix,
+    )?;
+    println!(
+        "{:>12} {} {} to {}",
+        style("Installed").cyan(),
+        project.name,
+        project.version,
+        prefix.display()
+    );
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    // Initialize the log backend and retrieve the argument matches
+/// Build the project printing its progress, or emitting the result of the
+/// build when the messages are in JSON
+fn compile_project(
+    config: &ProjectConfig,
+    profile: &ProfileArgs,
+    jobs: &JobsArgs,
+    examples: Option<Examples>,
+) -> Result<()> {
+    let it = Instant::now();
+    let project_name = &config.manifest()?.project.name;
+
+    if config.cli.message_format == MessageFormat::Json {
+        let result = build_project(config, profile.name(), jobs, examples);
+        Message::BuildFinished {
+            success: result.is_ok(),
+        }
+        .emit();
+        return result.map(|_| ());
+    }
+
+    // Print that compilation has started
+    println!("{:>12} {:?}", style("Compiling").cyan(), project_name);
+
+    // Build the project and retrieve a boolean that indicates if any
+    // source needed recompilation
+    let changes = build_project(config, profile.name(), jobs, examples)?;
+
+    // Print to console that compilation has finished
+    let detail = match changes {
+        false => String::from(" Already up to date"),
+        true => format!(" in {:.2}s", (Instant::now() - it).as_secs_f64()),
+    };
+    print_finished(config, profile.name(), &detail)
+}
+
+fn main() {
+    // Initialize the log backend
     pretty_env_logger::init();
 
-    // Extract all the configs
-    let mut config = ProjectConfig {
-        cli: Cli::parse(),
-        config: {
-            if !Path::new("Amargo.toml").is_file() {
-                None
-            } else {
-                let config_file_data = std::fs::read("Amargo.toml").unwrap();
-                toml::from_slice(&config_file_data[..]).ok()
+    if let Err(e) = run() {
+        eprintln!("{} {}", style("error:").red().bold(), e);
+        if let Some(hint) = e.hint() {
+            eprintln!("{} {}", style("help:").cyan().bold(), hint);
+        }
+        process::exit(e.exit_code());
+    }
+}
+
+/// Run the command of the cli
+fn run() -> Result<()> {
+    let mut cli = Cli::parse();
+    let invocation_dir = std::env::current_dir()
+        .and_then(|d| d.canonicalize())
+        .map_err(|e| Error::CurrentDirInvalid(PathBuf::from("."), e))?;
+    cli.commands.resolve_paths(&invocation_dir);
+
+    // `new` creates the project on the current directory, the rest of the
+    // commands work on the project that contains it (so `amargo b` works
+    // inside <project_name>/src/subdir) or on the one of `--manifest-path`
+    let manifest_path = match (&cli.commands, &cli.manifest_path) {
+        (Command::New { .. }, _) => None,
+        (_, Some(manifest_path)) => {
+            let manifest_path = invocation_dir.join(manifest_path);
+            if !manifest_path.is_file() {
+                return Err(Error::ManifestNotFound(manifest_path));
             }
+            Some(manifest_path)
         },
-        // TODO: Recursive ascend detect the project, for example you can be
-        // inside <project_name>/src/subdir and the `amargo b` still
-        // would need to work
-        working_dir: std::env::current_dir()
-            .and_then(|d| d.canonicalize())
-            .map_err(|e| Error::CurrentDirInvalid(PathBuf::from("."), e))?,
+        (_, None) => config::find_manifest(&invocation_dir),
+    };
+    let working_dir = manifest_path
+        .as_deref()
+        .and_then(Path::parent)
+        .map_or_else(|| invocation_dir.clone(), Path::to_path_buf);
+
+    // The paths of the build are relative to the root of the project
+    std::env::set_current_dir(&working_dir)
+        .map_err(|e| Error::CurrentDirInvalid(working_dir.clone(), e))?;
+
+    // Extract all the configs
+    let mut config = ProjectConfig {
+        cli,
+        config: manifest_path.as_deref().map(Config::load).transpose()?,
+        working_dir,
     };
 
     info!("Working dir {:?}", &config.working_dir);
@@ -118,123 +390,137 @@ fn main() -> Result<()> {
         Command::New {
             project_name,
             project_type,
+            lang,
         } => {
+            Project::validate_name(project_name).map_err(|reason| {
+                Error::InvalidProjectName(project_name.into(), reason)
+            })?;
+
             // Generate the config of the project
             config.config = Some(Config {
                 project: Project {
                     name: project_name.clone(),
+                    version: Project::default_version(),
+                    kind: *project_type,
+                    language: *lang,
+                    c_standard: None,
+                    cpp_standard: None,
                 },
+                build: Default::default(),
+                test: Default::default(),
+                profile: Default::default(),
+                dependencies: Default::default(),
             });
 
             info!("Creating project {} of kind {}", project_name, project_type);
             create_project(&config, *project_type)?;
         },
-        // Build the project in the provided `mode` on the cli
-        Command::Build { mode } => {
-            let it = Instant::now();
-            let project_name = &config.config.as_ref().unwrap().project.name;
-
-            // Check if this an amargo project
-            if !config.working_dir.join("Amargo.toml").is_file() {
-                println!("No project at {:?} found", config.working_dir);
-                std::process::exit(0);
-            }
+        // Build the project in the provided profile on the cli
+        Command::Build {
+            profile,
+            jobs,
+            examples,
+        } => {
+            let project_name = &config.manifest()?.project.name;
 
             info!("building {:?}", project_name);
 
-            // Print that compilation has started
-            println!("{:>12} {:?}", style("Compiling").cyan(), project_name);
-
-            // Build the project and retrieve a boolean that indicates if any
-            // source needed recompilation
-            let changes = build_project(&config, *mode)?;
-
-            // Print to console that compilation has finished
-            if !changes {
-                println!(
-                    "{:>12} {} {:?} Already up to date",
-                    style("Finished").cyan(),
-                    project_name,
-                    mode.to_string()
-                );
-            } else {
-                let elapsed = (Instant::now() - it).as_secs_f64();
-                println!(
-                    "{:>12} {} {} in {:.2}s",
-                    style("Finished").cyan(),
-                    project_name,
-                    mode,
-                    elapsed
-                );
-            }
+            let examples = match examples {
+                true => Some(Examples::All),
+                false => None,
+            };
+            compile_project(&config, profile, jobs, examples)?;
         },
-        Command::Run { mode, exe_args } => {
-            let it = Instant::now();
-            let project_name = &config.config.as_ref().unwrap().project.name;
-
-            // Check if this an amargo project
-            if !config.working_dir.join("Amargo.toml").is_file() {
-                println!("No project at {:?} found", config.working_dir);
-                std::process::exit(0);
-            }
+        Command::Run {
+            profile,
+            jobs,
+            example,
+            exe_args,
+        } => {
+            let project_name = &config.manifest()?.project.name;
 
             info!("Selected run option of {:?}", project_name);
 
-            // Print that compilation has started
-            println!("{:>12} {:?}", style("Compiling").cyan(), project_name);
+            // First compile the project (and the example to run)
+            let examples = example.as_deref().map(Examples::Only);
+            compile_project(&config, profile, jobs, examples)?;
 
-            // First compile the project.
-            let changes = build_project(&config, *mode)?;
+            // Generate the path to the executable, get the project name (that
+            // is the same as the executable name) or the example name
+            let executable_path = match example {
+                Some(example) => {
+                    profile.out_dir().join("examples").join(example)
+                },
+                None => profile.out_dir().join(project_name),
+            }
+            .with_extension(EXE_EXTENSION);
 
-            // Print to console that compilation has finished
-            if !changes {
-                println!(
-                    "{:>12} {} {} Already up to date",
-                    style("Finished").cyan(),
-                    project_name,
-                    mode
-                );
-            } else {
-                let elapsed = (Instant::now() - it).as_secs_f64();
+            // Print that the executable is being run
+            if config.cli.message_format == MessageFormat::Human {
                 println!(
-                    "{:>12} {} {} in {:.2}s",
-                    style("Finished").cyan(),
-                    project_name,
-                    mode,
-                    elapsed
+                    "{:>12} `{} {:?}`\n",
+                    style("Running").cyan(),
+                    executable_path.display(),
+                    exe_args.join(" ")
                 );
             }
 
-            // Generate the path to the executable, get the project name (that
-            // is the same as the executable name)
-            let executable_path = PathBuf::from(*mode)
-                .join(project_name)
-                .with_extension(EXE_EXTENSION);
-
-            // Print that the executable is being run
-            println!(
-                "{:>12} `{} {:?}`\n",
-                style("Running").cyan(),
-                executable_path.display(),
-                exe_args.join(" ")
-            );
-
             // Spawn the process of the binary application supplying the
             // arguments passed to `amargo` via `-- <args...>`
-            process::Command::new(&executable_path)
+            let executable_path = config.working_dir.join(executable_path);
+            let status = process::Command::new(&executable_path)
                 .args(exe_args)
+                .current_dir(&invocation_dir)
                 .status()
-                .unwrap();
+                .map_err(|e| {
+                    Error::ProcessCreation(executable_path.clone(), e)
+                })?;
+            if !status.success() {
+                return Err(Error::ProgramFailed(executable_path, status));
+            }
+        },
+        Command::Test { test, jobs } => {
+            test_project(&config, test, jobs)?;
+        },
+        Command::Bench {
+            filter,
+            samples,
+            jobs,
+        } => {
+            bench_project(&config, filter.as_deref(), *samples, jobs)?;
+        },
+        Command::Install { prefix, jobs } => {
+            let prefix = match prefix {
+                Some(prefix) => prefix.clone(),
+                None => install::default_prefix()?,
+            };
+            install_project(&config, &prefix, jobs)?;
+        },
+        Command::Uninstall {
+            project_name,
+            prefix,
+        } => {
+            // Without a name uninstall the current project
+            let project_name = match project_name {
+                Some(project_name) => project_name,
+                None => &config.manifest()?.project.name,
+            };
+
+            let prefix = match prefix {
+                Some(prefix) => prefix.clone(),
+                None => install::default_prefix()?,
+            };
+            install::uninstall(project_name, &prefix)?;
         },
         Command::Clean => {
             // Check if this an amargo project
-            if !config.working_dir.join("Amargo.toml").is_file() {
-                println!("No project at {:?} found", config.working_dir);
-                std::process::exit(0);
-            }
+            config.manifest()?;
 
-            fs::remove_dir_all(&config.working_dir.join("target"))
-                .expect("Cannot remove target dir");
+            let target_dir = config.working_dir.join("target");
+            if target_dir.is_dir() {
+                fs::remove_dir_all(&target_dir)
+                    .map_err(|e| Error::CannotRemove(target_dir, e))?;
+            }
         },
     };
 
@@ -0,0 +1,59 @@
+//! Machine readable messages emitted with `--message-format json`, one JSON
+//! object per line on stdout, tagged with a `reason` like the ones of cargo
+
+use std::path::{Path, PathBuf};
+
+use crate::{config::ProjectType, diagnostic::Diagnostic};
+
+/// The events of a build, named after the `reason` of the cargo ones
+#[allow(clippy::enum_variant_names)]
+#[derive(serde::Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub(crate) enum Message<'a> {
+    /// The compilation of a source has started
+    CompilerStarted { source: &'a Path, object: &'a Path },
+
+    /// The compilation of a source has finished
+    CompilerFinished {
+        source: &'a Path,
+        object: &'a Path,
+        success: bool,
+    },
+
+    /// A diagnostic reported by the compiler while compiling `source`
+    CompilerMessage {
+        source: &'a Path,
+        diagnostic: &'a Diagnostic,
+    },
+
+    /// An artifact (executable, library or single header) has been produced,
+    /// `fresh` if it was already up to date
+    CompilerArtifact {
+        target: &'a str,
+        kind: ProjectType,
+        filenames: Vec<PathBuf>,
+        fresh: bool,
+    },
+
+    /// The build has finished
+    BuildFinished { success: bool },
+
+    /// A test case has finished, with the reason of the `failure` (if it
+    /// failed) and its captured output
+    TestFinished {
+        name: &'a str,
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        failure: Option<&'a str>,
+        duration: f64,
+        stdout: &'a str,
+        stderr: &'a str,
+    },
+}
+
+impl Message<'_> {
+    /// Print the message as a single line of JSON
+    pub fn emit(&self) {
+        println!("{}", serde_json::to_string(self).unwrap());
+    }
+}
@@ -0,0 +1,52 @@
+//! A pool of worker threads that run a queue of work in order, used to keep
+//! several compilers running at the same time and to run the test cases
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// Run every one of the `pending` items with `run` on up to `workers`
+/// threads, when `stop` is true for some result no more items are started
+///
+/// Returns the results in the order they ended and the items left pending
+pub(crate) fn run<T, R, F, S>(
+    pending: VecDeque<T>,
+    workers: usize,
+    run: F,
+    stop: S,
+) -> (Vec<R>, VecDeque<T>)
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+    S: Fn(&R) -> bool + Sync,
+{
+    let workers = workers.max(1).min(pending.len());
+    let pending = Mutex::new(pending);
+    let results = Mutex::new(Vec::new());
+    let stopped = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while !stopped.load(Ordering::SeqCst) {
+                    let item = match pending.lock().unwrap().pop_front() {
+                        Some(item) => item,
+                        None => break,
+                    };
+                    let result = run(item);
+                    if stop(&result) {
+                        stopped.store(true, Ordering::SeqCst);
+                    }
+                    results.lock().unwrap().push(result);
+                }
+            });
+        }
+    });
+
+    (results.into_inner().unwrap(), pending.into_inner().unwrap())
+}
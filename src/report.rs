@@ -0,0 +1,221 @@
+//! The reports of `amargo test` for CI servers and report viewers, JUnit
+//! XML, TAP or JSON with the result, duration and output of every test
+
+use std::{fs, path::Path};
+
+use crate::{
+    config::ReportFormat,
+    error::*,
+    test::{TestOutcome, TestResult},
+};
+
+/// A test on the JSON report
+#[derive(serde::Serialize)]
+struct JsonTest<'a> {
+    name: &'a str,
+    program: &'a str,
+    outcome: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+
+    /// Duration in seconds
+    duration: f64,
+    stdout: &'a str,
+    stderr: &'a str,
+}
+
+/// Escape `text` to be used in XML contents or attributes, the characters
+/// XML doesn't allow are removed
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {},
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The JUnit XML report, a `testsuite` per test program
+fn junit(name: &str, results: &[TestResult]) -> String {
+    let failures = |results: &[&TestResult]| {
+        results
+            .iter()
+            .filter(|r| r.outcome != TestOutcome::Passed)
+            .count()
+    };
+    let time = |results: &[&TestResult]| {
+        results
+            .iter()
+            .map(|r| r.duration.as_secs_f64())
+            .sum::<f64>()
+    };
+
+    // Group the results by program keeping their order
+    let mut suites: Vec<(&str, Vec<&TestResult>)> = Vec::new();
+    for result in results {
+        match suites.iter_mut().find(|(p, _)| *p == result.program) {
+            Some((_, suite)) => suite.push(result),
+            None => suites.push((&result.program, vec![result])),
+        }
+    }
+
+    let all = results.iter().collect::<Vec<&TestResult>>();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        xml_escape(name),
+        all.len(),
+        failures(&all),
+        time(&all)
+    ));
+    for (program, suite) in suites {
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(program),
+            suite.len(),
+            failures(&suite),
+            time(&suite)
+        ));
+        for result in suite {
+            let case = result
+                .name
+                .strip_prefix(program)
+                .and_then(|n| n.strip_prefix("::"))
+                .unwrap_or(&result.name);
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(case),
+                xml_escape(program),
+                result.duration.as_secs_f64()
+            ));
+
+            let reason = match &result.outcome {
+                TestOutcome::Passed => {
+                    xml.push_str("/>\n");
+                    continue;
+                },
+                TestOutcome::Failed(reason) => reason,
+            };
+            xml.push_str(">\n");
+            xml.push_str(&format!(
+                "      <failure message=\"{}\"/>\n",
+                xml_escape(reason)
+            ));
+            for (tag, output) in [
+                ("system-out", &result.stdout),
+                ("system-err", &result.stderr),
+            ] {
+                if !output.is_empty() {
+                    xml.push_str(&format!(
+                        "      <{0}>{1}</{0}>\n",
+                        tag,
+                        xml_escape(output)
+                    ));
+                }
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+/// The TAP version 13 report, the details of the failed tests go on a YAML
+/// block
+fn tap(results: &[TestResult]) -> String {
+    let mut tap = format!("TAP version 13\n1..{}\n", results.len());
+    for (i, result) in results.iter().enumerate() {
+        let reason = match &result.outcome {
+            TestOutcome::Passed => {
+                tap.push_str(&format!("ok {} - {}\n", i + 1, result.name));
+                continue;
+            },
+            TestOutcome::Failed(reason) => reason,
+        };
+
+        tap.push_str(&format!("not ok {} - {}\n", i + 1, result.name));
+        tap.push_str("  ---\n");
+        tap.push_str(&format!(
+            "  message: {}\n",
+            serde_json::to_string(reason).unwrap()
+        ));
+        tap.push_str(&format!(
+            "  duration_ms: {:.3}\n",
+            result.duration.as_secs_f64() * 1000.0
+        ));
+        for (key, output) in
+            [("stdout", &result.stdout), ("stderr", &result.stderr)]
+        {
+            if !output.is_empty() {
+                tap.push_str(&format!("  {}: |\n", key));
+                for line in output.lines() {
+                    tap.push_str(&format!("    {}\n", line));
+                }
+            }
+        }
+        tap.push_str("  ...\n");
+    }
+
+    tap
+}
+
+/// The JSON report, an array with an object per test
+fn json(results: &[TestResult]) -> String {
+    let tests = results
+        .iter()
+        .map(|result| {
+            let (outcome, reason) = match &result.outcome {
+                TestOutcome::Passed => ("passed", None),
+                TestOutcome::Failed(reason) => ("failed", Some(&reason[..])),
+            };
+            JsonTest {
+                name: &result.name,
+                program: &result.program,
+                outcome,
+                reason,
+                duration: result.duration.as_secs_f64(),
+                stdout: &result.stdout,
+                stderr: &result.stderr,
+            }
+        })
+        .collect::<Vec<JsonTest>>();
+
+    let mut json = serde_json::to_string_pretty(&tests).unwrap();
+    json.push('\n');
+
+    json
+}
+
+/// Write the report of the `results` of the tests of the project `name` at
+/// `path`
+pub(crate) fn write(
+    path: &Path,
+    format: ReportFormat,
+    name: &str,
+    results: &[TestResult],
+) -> Result<()> {
+    let data = match format {
+        ReportFormat::Junit => junit(name, results),
+        ReportFormat::Tap => tap(results),
+        ReportFormat::Json => json(results),
+    };
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|e| Error::CannotCreate(dir.to_path_buf(), e))?;
+    }
+    fs::write(path, data)
+        .map_err(|e| Error::CannotCreate(path.to_path_buf(), e))
+}
@@ -0,0 +1,97 @@
+//! The build state of a profile, persisted on `target/<profile>` between
+//! builds, records how each object and the target were generated so they are
+//! only generated again when something that affects them changes
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::error::*;
+
+use log::info;
+
+/// Name of the file where the state is stored inside `target/<profile>`
+const STATE_FILE: &str = ".amargo-state.toml";
+
+/// The state of the last build of a profile
+#[derive(Default, Clone, serde::Deserialize, serde::Serialize)]
+pub(crate) struct BuildState {
+    /// The headers grouped into the single header of header-only projects
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<PathBuf>,
+
+    /// How the target was linked (or archived)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkState>,
+
+    /// The objects compiled indexed by their path
+    #[serde(default)]
+    pub objects: BTreeMap<String, ObjectState>,
+
+    /// How the programs of the tests, benchmarks and examples were linked
+    /// indexed by their path
+    #[serde(default)]
+    pub programs: BTreeMap<String, LinkState>,
+}
+
+/// How an object was compiled
+#[derive(Clone, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct ObjectState {
+    /// The source compiled into the object
+    pub source: PathBuf,
+
+    /// The full command line used to compile it
+    pub command: Vec<String>,
+
+    /// The identity of the compiler (path and version)
+    pub compiler: String,
+
+    /// The dependencies reported by the compiler (including the source)
+    pub deps: Vec<PathBuf>,
+}
+
+/// How the target was linked
+#[derive(Clone, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct LinkState {
+    /// The path of the target
+    pub target: PathBuf,
+
+    /// The full command line used to generate it
+    pub command: Vec<String>,
+}
+
+impl BuildState {
+    /// Load the state stored on `out_dir`, if it doesn't exist or it's
+    /// corrupted the state is empty so everything is built again
+    pub fn load(out_dir: &Path) -> Self {
+        let path = out_dir.join(STATE_FILE);
+        let state = fs::read(&path)
+            .ok()
+            .and_then(|data| toml::from_slice(&data[..]).ok());
+        if state.is_none() && path.exists() {
+            info!("Ignoring invalid build state at {:?}", path);
+        }
+
+        state.unwrap_or_default()
+    }
+
+    /// Store the state on `out_dir`
+    pub fn save(&self, out_dir: &Path) -> Result<()> {
+        let path = out_dir.join(STATE_FILE);
+        let data = toml::to_string(self).unwrap();
+
+        fs::write(&path, data).map_err(|e| Error::CannotCreate(path, e))
+    }
+}
+
+/// The full command line of `command`, used to detect when the way to build
+/// something changes
+pub(crate) fn command_line(command: &Command) -> Vec<String> {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
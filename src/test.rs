@@ -0,0 +1,471 @@
+//! The test harness of `amargo test`, every source of `tests/` is a test
+//! program that either has its own `main` or declares test cases with the
+//! `TEST(name)` macro of the bundled `amargo_test.h`, in that case amargo
+//! generates a `main` that runs the case whose name receives as argument
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{self, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{MessageFormat, ProjectConfig},
+    error::*,
+    message::Message,
+    pool,
+};
+
+use console::style;
+use regex::Regex;
+
+/// A bundled harness, a header with the macro that declares the cases of a
+/// program and the `main` that runs them
+pub(crate) struct Harness {
+    /// Name of the header
+    pub header: &'static str,
+
+    /// Contents of the header
+    pub data: &'static str,
+
+    /// The macro that declares a case, like `TEST` for `TEST(name)`, it must
+    /// declare a function `amargo_<macro in lowercase>_<name>`
+    pub macro_name: &'static str,
+
+    /// The `main` of the runner, the cases are on `amargo_cases` with their
+    /// `name` and the function that `run`s them
+    pub main: &'static str,
+}
+
+/// The harness of the tests, the case is selected by the first argument (or
+/// all of them if there's none)
+pub(crate) const TEST_HARNESS: Harness = Harness {
+    header: "amargo_test.h",
+    data: include_str!("../templates/amargo_test.h"),
+    macro_name: "TEST",
+    main: "int main(int argc, char **argv) {\n\
+           \x20   size_t i;\n\
+           \x20   int found = 0;\n\
+           \x20   for (i = 0; i < sizeof(amargo_cases) / sizeof(amargo_cases[0]); i++) {\n\
+           \x20       if (argc < 2 || strcmp(argv[1], amargo_cases[i].name) == 0) {\n\
+           \x20           amargo_cases[i].run();\n\
+           \x20           found = 1;\n\
+           \x20       }\n\
+           \x20   }\n\
+           \x20   return found ? 0 : 2;\n\
+           }\n",
+};
+
+/// A program of `tests/` (or `benches/`) already built
+pub(crate) struct TestProgram {
+    /// The path of the source relative to its directory without extension
+    pub name: String,
+
+    /// The path of the executable
+    pub path: PathBuf,
+
+    /// The cases declared with the macro of the harness, if there are none
+    /// the program is a case by itself
+    pub cases: Vec<String>,
+}
+
+impl TestProgram {
+    /// The cases of the program with their full name `<program>::<case>`, a
+    /// program without cases is a case by itself named after the program
+    pub fn named_cases(&self) -> Vec<(Option<&str>, String)> {
+        if self.cases.is_empty() {
+            return vec![(None, self.name.clone())];
+        }
+
+        self.cases
+            .iter()
+            .map(|case| (Some(&case[..]), format!("{}::{}", self.name, case)))
+            .collect()
+    }
+}
+
+/// Write `data` to `path` only if it changed, so the sources that depend on
+/// it are not compiled again
+pub(crate) fn write_if_changed(path: &Path, data: &str) -> Result<()> {
+    if fs::read_to_string(path).ok().as_deref() == Some(data) {
+        return Ok(());
+    }
+
+    let dir = path.parent().unwrap();
+    fs::create_dir_all(dir)
+        .map_err(|e| Error::CannotCreate(dir.to_path_buf(), e))?;
+    fs::write(path, data)
+        .map_err(|e| Error::CannotCreate(path.to_path_buf(), e))
+}
+
+impl Harness {
+    /// Write the header of the harness on `dir`
+    pub fn write_header(&self, dir: &Path) -> Result<()> {
+        write_if_changed(&dir.join(self.header), self.data)
+    }
+
+    /// Find the names of the cases declared with the macro on `source`
+    pub fn discover(&self, source: &Path) -> Result<Vec<String>> {
+        let data = fs::read_to_string(source)
+            .map_err(|e| Error::CannotRead(source.to_path_buf(), e))?;
+        let re = Regex::new(&format!(
+            r"(?m)^\s*{}\s*\(\s*(?P<name>[A-Za-z_]\w*)\s*\)",
+            self.macro_name
+        ))
+        .unwrap();
+
+        Ok(re
+            .captures_iter(&data)
+            .map(|cap| cap["name"].to_string())
+            .collect())
+    }
+
+    /// Write at `path` the source that includes `source` and defines the
+    /// `main` that runs its `cases`
+    pub fn write_runner(
+        &self,
+        path: &Path,
+        source: &Path,
+        cases: &[String],
+    ) -> Result<()> {
+        let mut data = format!(
+            "/* Generated by amargo, runs the cases of {0} */\n\
+             #include \"{0}\"\n\
+             \n\
+             #include <string.h>\n\
+             \n\
+             static const struct {{\n\
+             \x20   const char *name;\n\
+             \x20   void (*run)(void);\n\
+             }} amargo_cases[] = {{\n",
+            source.display().to_string().replace('\\', "/")
+        );
+        let prefix = self.macro_name.to_lowercase();
+        for case in cases {
+            data.push_str(&format!(
+                "    {{\"{0}\", amargo_{1}_{0}}},\n",
+                case, prefix
+            ));
+        }
+        data.push_str("};\n\n");
+        data.push_str(self.main);
+
+        write_if_changed(path, &data)
+    }
+}
+
+/// How `run` executes the test cases
+pub(crate) struct RunOptions<'a> {
+    /// Only the cases whose name contains it are run
+    pub filter: Option<&'a str>,
+
+    /// Number of cases running at the same time
+    pub threads: usize,
+
+    /// Stop starting cases after the first failure
+    pub fail_fast: bool,
+}
+
+/// A single test case to run, a program without `TEST(name)` cases is run
+/// as a whole
+struct TestCase<'a> {
+    program: &'a TestProgram,
+    case: Option<&'a str>,
+    name: String,
+    timeout: Duration,
+}
+
+/// How a test case ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TestOutcome {
+    Passed,
+
+    /// The case failed, with the reason (exit status, signal or timeout)
+    Failed(String),
+}
+
+/// The result of running a test case
+pub(crate) struct TestResult {
+    /// The full name of the case, `<program>::<case>`
+    pub name: String,
+
+    /// The name of the program of the case
+    pub program: String,
+
+    pub outcome: TestOutcome,
+    pub duration: Duration,
+
+    /// The captured stdout of the case
+    pub stdout: String,
+
+    /// The captured stderr of the case
+    pub stderr: String,
+}
+
+impl TestResult {
+    /// Emit the result as a JSON message
+    fn emit(&self) {
+        let failure = match &self.outcome {
+            TestOutcome::Passed => None,
+            TestOutcome::Failed(reason) => Some(&reason[..]),
+        };
+        Message::TestFinished {
+            name: &self.name,
+            success: failure.is_none(),
+            failure,
+            duration: self.duration.as_secs_f64(),
+            stdout: &self.stdout,
+            stderr: &self.stderr,
+        }
+        .emit();
+    }
+}
+
+/// Read all the `pipe` on another thread so the process never blocks writing
+fn read_pipe<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        let _ = pipe.read_to_end(&mut data);
+        String::from_utf8_lossy(&data).into_owned()
+    })
+}
+
+/// The name of the `signal` that terminated a process
+#[cfg(unix)]
+fn signal_name(signal: i32) -> Option<&'static str> {
+    match signal {
+        4 => Some("SIGILL"),
+        5 => Some("SIGTRAP"),
+        6 => Some("SIGABRT"),
+        8 => Some("SIGFPE"),
+        9 => Some("SIGKILL"),
+        #[cfg(target_os = "linux")]
+        7 => Some("SIGBUS"),
+        #[cfg(not(target_os = "linux"))]
+        10 => Some("SIGBUS"),
+        11 => Some("SIGSEGV"),
+        13 => Some("SIGPIPE"),
+        15 => Some("SIGTERM"),
+        _ => None,
+    }
+}
+
+/// Describe why a process that didn't exit successfully failed, the crashes
+/// are reported by the signal (or the exception code on Windows) name
+fn failure_reason(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return match signal_name(signal) {
+                Some(name) => format!("signal: {}, {}", signal, name),
+                None => format!("signal: {}", signal),
+            };
+        }
+    }
+
+    match status.code() {
+        #[cfg(windows)]
+        Some(code) if code as u32 & 0xC000_0000 == 0xC000_0000 => {
+            let name = match code as u32 {
+                0xC000_0005 => "STATUS_ACCESS_VIOLATION",
+                0xC000_0094 => "STATUS_INTEGER_DIVIDE_BY_ZERO",
+                0xC000_00FD => "STATUS_STACK_OVERFLOW",
+                0xC000_0409 => "STATUS_STACK_BUFFER_OVERRUN",
+                _ => "exception",
+            };
+            format!("exit code: {:#X}, {}", code as u32, name)
+        },
+        Some(code) => format!("exit status: {}", code),
+        None => String::from("terminated"),
+    }
+}
+
+/// Run a test case capturing its output, it's killed if it runs for longer
+/// than its timeout
+fn run_case(case: &TestCase) -> Result<TestResult> {
+    let start = Instant::now();
+    let mut child = process::Command::new(&case.program.path)
+        .args(case.case)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::ProcessCreation(case.program.path.clone(), e))?;
+    let stdout = read_pipe(child.stdout.take().unwrap());
+    let stderr = read_pipe(child.stderr.take().unwrap());
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(Error::ProcessExec)? {
+            break Some(status);
+        }
+        if start.elapsed() >= case.timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    let outcome = match status {
+        Some(status) if status.success() => TestOutcome::Passed,
+        Some(status) => TestOutcome::Failed(failure_reason(status)),
+        None => TestOutcome::Failed(format!(
+            "timed out after {}s",
+            case.timeout.as_secs_f64()
+        )),
+    };
+
+    Ok(TestResult {
+        name: case.name.clone(),
+        program: case.program.name.clone(),
+        outcome,
+        duration: start.elapsed(),
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    })
+}
+
+/// Run the test cases of the `programs` (each one on its own process) that
+/// match the filter and print their result, the output of the cases is only
+/// shown if they fail (with JSON messages only the result of every case is
+/// emitted)
+pub(crate) fn run(
+    config: &ProjectConfig,
+    programs: &[TestProgram],
+    options: &RunOptions,
+) -> Result<Vec<TestResult>> {
+    let start = Instant::now();
+    let all_cases = programs
+        .iter()
+        .flat_map(|program| {
+            program.named_cases().into_iter().map(move |(case, name)| {
+                TestCase {
+                    program,
+                    case,
+                    timeout: config.test_timeout(&name),
+                    name,
+                }
+            })
+        })
+        .collect::<Vec<TestCase>>();
+    let total = all_cases.len();
+    let cases = all_cases
+        .into_iter()
+        .filter(|case| options.filter.is_none_or(|f| case.name.contains(f)))
+        .enumerate()
+        .collect::<VecDeque<(usize, TestCase)>>();
+    let filtered_out = total - cases.len();
+
+    let human = config.cli.message_format == MessageFormat::Human;
+    if human {
+        println!(
+            "\nrunning {} {}",
+            cases.len(),
+            if cases.len() == 1 { "test" } else { "tests" }
+        );
+    }
+
+    // Keep `options.threads` cases running, printing the result of each one
+    // as soon as it ends
+    let passed = |result: &Result<TestResult>| {
+        matches!(
+            result,
+            Ok(TestResult {
+                outcome: TestOutcome::Passed,
+                ..
+            })
+        )
+    };
+    let (mut results, not_run) = pool::run(
+        cases,
+        options.threads,
+        |(index, case)| {
+            let result = run_case(&case);
+            match (&result, human) {
+                (Ok(result), false) => result.emit(),
+                (Err(_), false) => {},
+                (_, true) => println!(
+                    "test {} ... {}",
+                    case.name,
+                    match passed(&result) {
+                        true => style("ok").green(),
+                        false => style("FAILED").red(),
+                    }
+                ),
+            }
+            (index, result)
+        },
+        |(_, result)| options.fail_fast && !passed(result),
+    );
+    let not_run = not_run.len();
+
+    // Keep the results in the order of the cases
+    results.sort_by_key(|(index, _)| *index);
+    let results = results
+        .into_iter()
+        .map(|(_, result)| result)
+        .collect::<Result<Vec<TestResult>>>()?;
+
+    if !human {
+        return Ok(results);
+    }
+
+    // Show the output of the failed cases and a summary
+    let failed = results
+        .iter()
+        .filter(|r| r.outcome != TestOutcome::Passed)
+        .collect::<Vec<&TestResult>>();
+    // The output is only listed if some failed case has any
+    let has_output = failed
+        .iter()
+        .any(|r| !r.stdout.is_empty() || !r.stderr.is_empty());
+    if has_output {
+        println!("\nfailures:");
+        for result in &failed {
+            for (stream, output) in
+                [("stdout", &result.stdout), ("stderr", &result.stderr)]
+            {
+                if !output.is_empty() {
+                    println!("\n---- {} {} ----", result.name, stream);
+                    print!("{}", output);
+                }
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        println!("\nfailures:");
+        for result in &failed {
+            if let TestOutcome::Failed(reason) = &result.outcome {
+                println!("    {} ({})", result.name, reason);
+            }
+        }
+    }
+
+    let verdict = match failed.is_empty() {
+        true => style("ok").green(),
+        false => style("FAILED").red(),
+    };
+    let mut summary = format!(
+        "{} passed; {} failed; {} filtered out",
+        results.len() - failed.len(),
+        failed.len(),
+        filtered_out
+    );
+    if not_run > 0 {
+        summary.push_str(&format!("; {} not run", not_run));
+    }
+    println!(
+        "\ntest result: {}. {}; finished in {:.2}s\n",
+        verdict,
+        summary,
+        start.elapsed().as_secs_f64()
+    );
+
+    Ok(results)
+}
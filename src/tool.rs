@@ -1,49 +1,71 @@
 use std::{
     ffi::OsString,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, Stdio},
 };
 
-use crate::{build::Object, error::*};
+use crate::{
+    build::Object,
+    config::{Language, OptLevel},
+    error::*,
+};
 
-/// Find an avaible tool on the system
+/// Find an avaible tool on the system for the `language`, for C++ the C++
+/// drivers are used (`clang++`, `g++`) so the C++ runtime is linked
 /// TODO: On windows try to put mscv on the environment first
-fn find_tool() -> Result<(PathBuf, ToolFamily)> {
-    // Macro that checks if command exists
-    macro_rules! exists_command {
-        ($command_name:literal) => {
-            Command::new($command_name).arg("-v").output().is_ok()
-        };
-    }
+fn find_tool(language: Language) -> Result<(PathBuf, ToolFamily)> {
+    let (clang, gcc) = match language {
+        Language::C => ("clang", "gcc"),
+        Language::Cpp => ("clang++", "g++"),
+    };
 
     // Check with priorities, and retrieve the full compiler path and the
     // ToolFamily
     //  * first: clang,
     //  * second: Windows -> clang-cl _ -> Gnu
     //  * third Windows -> msvc
-    if exists_command!("clang") {
-        Ok((which::which("clang").unwrap(), ToolFamily::Clang))
-    } else if cfg!(target_os = "windows") {
-        if exists_command!("clang-cl") {
-            Ok((
-                which::which("clang-cl").unwrap(),
-                ToolFamily::Msvc { clang_cl: true },
-            ))
-        } else if exists_command!("cl") {
-            Ok((
-                which::which("cl").unwrap(),
-                ToolFamily::Msvc { clang_cl: false },
-            ))
-        } else if exists_command!("gcc") {
-            Ok((which::which("gcc").unwrap(), ToolFamily::Gnu))
-        } else {
-            Err(Error::NoCompilerFound)
-        }
-    } else if exists_command!("gcc") {
-        Ok((which::which("gcc").unwrap(), ToolFamily::Gnu))
+    let candidates: &[(&str, ToolFamily)] = if cfg!(target_os = "windows") {
+        &[
+            (clang, ToolFamily::Clang),
+            ("clang-cl", ToolFamily::Msvc { clang_cl: true }),
+            ("cl", ToolFamily::Msvc { clang_cl: false }),
+            (gcc, ToolFamily::Gnu),
+        ]
     } else {
-        Err(Error::NoCompilerFound)
+        &[(clang, ToolFamily::Clang), (gcc, ToolFamily::Gnu)]
+    };
+
+    candidates
+        .iter()
+        .find_map(|(name, family)| {
+            which::which(name).ok().map(|path| (path, *family))
+        })
+        .ok_or(Error::NoCompilerFound(language))
+}
+
+/// Get the path and the version of the compiler at `path`, `cl.exe` prints
+/// its version on the banner when is called without arguments
+fn compiler_identity(path: &Path, family: ToolFamily) -> String {
+    let mut cmd = Command::new(path);
+    if !matches!(family, ToolFamily::Msvc { clang_cl: false }) {
+        cmd.arg("--version");
     }
+    let version = cmd
+        .output()
+        .map(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            stdout
+                .lines()
+                .chain(stderr.lines())
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    format!("{} ({})", path.display(), version)
 }
 
 /// Configuration used to represent an invocation of a C compiler (or another
@@ -62,35 +84,43 @@ pub(crate) struct Tool {
     /// Arguments added
     args: Vec<OsString>,
 
+    /// Arguments added only when linking
+    link_args: Vec<OsString>,
+
     /// Specifies the family, needed as some flags differ between compiler
     /// families
     pub family: ToolFamily,
-}
 
-impl Default for Tool {
-    fn default() -> Self {
-        let (path, family) = find_tool().unwrap();
+    /// The language this tool compiles
+    pub language: Language,
 
-        Tool {
-            path,
-            args: Vec::new(),
-            family,
-        }
-    }
+    /// Path and version of the compiler, used to know when the compiler
+    /// changes between builds
+    pub identity: String,
 }
 
 impl Tool {
-    /// Instantiates a new tool given the compiler `path`
-    pub fn new() -> Self {
+    /// Instantiates a new tool for the `language`
+    pub fn new(language: Language) -> Result<Self> {
         // Extract the compiler family and path
         // TODO: First try to retrieve this from the config file
-        let (path, family) = find_tool().unwrap();
+        let (path, family) = find_tool(language)?;
 
-        Tool {
+        let mut tool = Tool {
+            identity: compiler_identity(&path, family),
             path,
             args: Vec::new(),
+            link_args: Vec::new(),
             family,
+            language,
+        };
+        if language == Language::Cpp {
+            tool.args.extend(
+                family.cpp_flags().iter().map(|&flag| OsString::from(flag)),
+            );
         }
+
+        Ok(tool)
     }
 
     /// Add an arbitrary argument
@@ -98,51 +128,220 @@ impl Tool {
         self.args.push(arg);
     }
 
+    /// Select the `standard` of the `language`, checking first that this
+    /// family knows it and that the compiler actually supports it
+    pub fn push_standard(&mut self, standard: &str) -> Result<()> {
+        let language = self.language;
+        let unsupported =
+            || Error::UnsupportedStandard(standard.into(), self.path.clone());
+        let flag = self
+            .family
+            .std_flag(language, standard)
+            .ok_or_else(unsupported)?;
+
+        // Preprocess an empty source with the flag to check the compiler
+        // accepts it, `cl.exe` only warns on unknown flags so for msvc the
+        // list of known standards is trusted
+        if let ToolFamily::Gnu | ToolFamily::Clang = self.family {
+            let language_name = match language {
+                Language::C => "c",
+                Language::Cpp => "c++",
+            };
+            let accepted = Command::new(&self.path)
+                .args(["-x", language_name, &flag, "-E", "-"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_err(|e| Error::ProcessCreation(self.path.clone(), e))?
+                .success();
+            if !accepted {
+                return Err(unsupported());
+            }
+        }
+        self.args.push(flag.into());
+
+        Ok(())
+    }
+
+    /// Add an argument only if there is one, useful for the flags that only
+    /// some families need
+    pub fn push_cc_arg_opt(&mut self, arg: Option<&str>) {
+        self.args.extend(arg.map(OsString::from));
+    }
+
+    /// Add an arbitrary argument only used when linking
+    pub fn push_ld_arg(&mut self, arg: OsString) {
+        self.link_args.push(arg);
+    }
+
     /// Converts this compiler into a `Command` that's ready to build objects
     ///
     /// This is useful for when the compiler needs to be executed and the
     /// command returned will already have the initial arguments and environment
     /// variables configured.
-    pub fn to_build_command(&self, include_dirs: &[PathBuf]) -> Command {
+    ///
+    /// The dependencies discovered by the compiler are written to `depfile`
+    /// (or printed to stdout with MSVC)
+    pub fn to_build_command(
+        &self,
+        include_dirs: &[PathBuf],
+        depfile: &Path,
+    ) -> Command {
         let include_dirs = include_dirs
             .iter()
             .map(|p| {
-                let mut inc = p.to_str().unwrap().to_string();
-                inc.insert_str(0, self.family.include_flag());
+                let mut inc = OsString::from(self.family.include_flag());
+                inc.push(p);
                 inc
             })
-            .collect::<Vec<String>>();
+            .collect::<Vec<OsString>>();
         let mut cmd = Command::new(&self.path);
         cmd.args(&self.args);
         cmd.args(include_dirs);
+        cmd.args(self.family.depfile_flags(depfile));
         cmd.args(self.family.compilation_flags());
         cmd
     }
 
     /// Converts this compiler into a `Command` that's ready to link
     ///
-    /// TODO: Support linker flags, and check if the warning level affects
-    /// here if we are just linking objects
+    /// TODO: Check if the warning level affects here if we are just linking
+    /// objects
     /// TODO: Support adding external libraries
     pub fn to_link_command(
         &self,
         exe_path: impl AsRef<Path>,
         objects: &[Object],
     ) -> Command {
-        // FIXME: Is really needed to convert to String, shouldn't Command::args
-        // accept also a PathBuf?
-        let objects = objects
-            .iter()
-            .map(|o| o.path.to_str().unwrap().to_string())
-            .collect::<Vec<String>>();
         let mut cmd = Command::new(&self.path);
-        cmd.args(objects);
+        cmd.args(objects.iter().map(|o| &o.path));
+        cmd.arg(self.family.exe_flag());
+        cmd.arg(exe_path.as_ref());
+        self.push_link_args(&mut cmd, Vec::new());
+        cmd
+    }
+
+    /// Converts this compiler into a `Command` that's ready to link a dynamic
+    /// library, optionally with a `soname` and generating an import library
+    pub fn to_shared_link_command(
+        &self,
+        lib_path: impl AsRef<Path>,
+        soname: Option<&str>,
+        import_lib: Option<&Path>,
+        objects: &[Object],
+    ) -> Command {
+        let mut cmd = Command::new(&self.path);
+        cmd.arg(self.family.shared_flag());
+        cmd.args(objects.iter().map(|o| &o.path));
         cmd.arg(self.family.exe_flag());
-        cmd.arg(exe_path.as_ref().to_str().unwrap());
+        cmd.arg(lib_path.as_ref());
+
+        let mut link_args = Vec::new();
+        link_args.extend(soname.and_then(|s| self.family.soname_flag(s)));
+        link_args.extend(import_lib.map(|p| self.family.import_lib_flag(p)));
+        self.push_link_args(&mut cmd, link_args);
+        cmd
+    }
+
+    /// Add `extra` and the linker arguments to the link `cmd`
+    fn push_link_args(&self, cmd: &mut Command, mut extra: Vec<OsString>) {
+        extra.extend(self.link_args.iter().cloned());
+
+        // On msvc the linker args must go after `/link`
+        if !extra.is_empty() {
+            if let ToolFamily::Msvc { .. } = self.family {
+                cmd.arg("/link");
+            }
+            cmd.args(extra);
+        }
+    }
+}
+
+/// Find an avaible archiver on the system that matches the compiler `family`
+fn find_archiver(family: ToolFamily) -> Result<(PathBuf, ArchiverFamily)> {
+    // Check with priorities, and retrieve the full archiver path and the
+    // ArchiverFamily
+    //  * msvc: lib.exe (llvm-lib first for clang-cl)
+    //  * clang: llvm-ar then ar
+    //  * gnu: ar
+    let candidates: &[(&str, ArchiverFamily)] = match family {
+        ToolFamily::Msvc { clang_cl: true } => &[
+            ("llvm-lib", ArchiverFamily::Lib),
+            ("lib", ArchiverFamily::Lib),
+        ],
+        ToolFamily::Msvc { clang_cl: false } => &[("lib", ArchiverFamily::Lib)],
+        ToolFamily::Clang => {
+            &[("llvm-ar", ArchiverFamily::Ar), ("ar", ArchiverFamily::Ar)]
+        },
+        ToolFamily::Gnu => &[("ar", ArchiverFamily::Ar)],
+    };
+
+    candidates
+        .iter()
+        .find_map(|(name, family)| {
+            which::which(name).ok().map(|path| (path, *family))
+        })
+        .ok_or(Error::NoArchiverFound)
+}
+
+/// Abstraction over the tool that groups objects into a static library
+#[derive(Clone, Debug)]
+pub(crate) struct Archiver {
+    /// Path to the archiver
+    pub path: PathBuf,
+
+    /// Specifies the family, `ar` and `lib.exe` have different command
+    /// signatures
+    pub family: ArchiverFamily,
+}
+
+impl Archiver {
+    /// Instantiates the archiver that goes along with the compiler `family`
+    pub fn new(family: ToolFamily) -> Result<Self> {
+        let (path, family) = find_archiver(family)?;
+
+        Ok(Archiver { path, family })
+    }
+
+    /// Converts this archiver into a `Command` that's ready to create the
+    /// static library at `lib_path` from `objects`
+    ///
+    /// NOTE: `ar` adds the objects to an existing archive, so the old one
+    /// should be removed first
+    pub fn to_archive_command(
+        &self,
+        lib_path: impl AsRef<Path>,
+        objects: &[Object],
+    ) -> Command {
+        let mut cmd = Command::new(&self.path);
+        match self.family {
+            ArchiverFamily::Ar => {
+                cmd.arg("crs");
+                cmd.arg(lib_path.as_ref());
+            },
+            ArchiverFamily::Lib => {
+                let mut out = OsString::from("/OUT:");
+                out.push(lib_path.as_ref());
+                cmd.arg("/nologo");
+                cmd.arg(out);
+            },
+        }
+        cmd.args(objects.iter().map(|o| &o.path));
         cmd
     }
 }
 
+/// Represents the family of archivers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ArchiverFamily {
+    /// GNU `ar` or `llvm-ar`
+    Ar,
+
+    /// MSVC `lib.exe` or `llvm-lib`
+    Lib,
+}
+
 /// Represents the family of tools this tool belongs to.
 ///
 /// Each family of tools differs in how and what arguments they accept.
@@ -173,11 +372,73 @@ impl ToolFamily {
         }
     }
 
-    /// Compilation in release mode
-    pub fn release_flags(&self) -> &'static str {
+    /// Get the flags needed to compile C++, `cl.exe` needs to be told to
+    /// treat the sources as C++ and to enable the C++ exceptions
+    pub fn cpp_flags(&self) -> &'static [&'static str] {
+        match *self {
+            ToolFamily::Msvc { .. } => &["/TP", "/EHsc"],
+            _ => &[],
+        }
+    }
+
+    /// Get the flag that selects the `standard` of the `language`, or `None`
+    /// if the family doesn't know that standard
+    pub fn std_flag(
+        &self,
+        language: Language,
+        standard: &str,
+    ) -> Option<String> {
+        let known: &[&str] = match (*self, language) {
+            (ToolFamily::Msvc { .. }, Language::C) => {
+                &["c11", "c17", "clatest"]
+            },
+            (ToolFamily::Msvc { .. }, Language::Cpp) => {
+                &["c++14", "c++17", "c++20", "c++latest"]
+            },
+            (_, Language::C) => &[
+                "c89", "c90", "c99", "c11", "c17", "c18", "c2x", "c23",
+                "gnu89", "gnu90", "gnu99", "gnu11", "gnu17", "gnu18", "gnu2x",
+                "gnu23",
+            ],
+            (_, Language::Cpp) => &[
+                "c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++2b",
+                "c++23", "gnu++98", "gnu++03", "gnu++11", "gnu++14", "gnu++17",
+                "gnu++20", "gnu++2b", "gnu++23",
+            ],
+        };
+        if !known.contains(&standard) {
+            return None;
+        }
+
+        match *self {
+            ToolFamily::Msvc { .. } => Some(format!("/std:{}", standard)),
+            _ => Some(format!("-std={}", standard)),
+        }
+    }
+
+    /// Get the flag of an optimization level
+    pub fn opt_level_flag(&self, opt_level: OptLevel) -> &'static str {
+        match (*self, opt_level) {
+            (ToolFamily::Msvc { .. }, OptLevel::O0) => "/Od",
+            (ToolFamily::Msvc { .. }, OptLevel::O1) => "/O1",
+            (ToolFamily::Msvc { .. }, OptLevel::O2 | OptLevel::O3) => "/O2",
+            (ToolFamily::Msvc { .. }, OptLevel::Size | OptLevel::MinSize) => {
+                "/O1"
+            },
+            (_, OptLevel::O0) => "-O0",
+            (_, OptLevel::O1) => "-O1",
+            (_, OptLevel::O2) => "-O2",
+            (_, OptLevel::O3) => "-O3",
+            (_, OptLevel::Size) => "-Os",
+            (_, OptLevel::MinSize) => "-Oz",
+        }
+    }
+
+    /// Get the flag to add a preprocessor define
+    pub fn define_flag(&self) -> &'static str {
         match *self {
-            ToolFamily::Msvc { .. } => "/O2",
-            ToolFamily::Gnu | ToolFamily::Clang => "-O3",
+            ToolFamily::Msvc { .. } => "/D",
+            _ => "-D",
         }
     }
 
@@ -189,6 +450,15 @@ impl ToolFamily {
         }
     }
 
+    /// Get the flags to output the dependencies (the included headers) of a
+    /// source, msvc can only print them to stdout
+    pub fn depfile_flags(&self, depfile: &Path) -> Vec<OsString> {
+        match *self {
+            ToolFamily::Msvc { .. } => vec!["/showIncludes".into()],
+            _ => vec!["-MMD".into(), "-MF".into(), depfile.into()],
+        }
+    }
+
     /// Get the compilation flags variant
     pub fn compilation_flags(&self) -> &'static [&'static str] {
         match *self {
@@ -197,6 +467,102 @@ impl ToolFamily {
         }
     }
 
+    /// Get the file name of the static library of `name`
+    pub fn static_lib_name(&self, name: &str) -> String {
+        match *self {
+            ToolFamily::Msvc { .. } => format!("{}.lib", name),
+            _ => format!("lib{}.a", name),
+        }
+    }
+
+    /// Get the file names of the dynamic library of `name`, the first one is
+    /// the real library and the rest are symlinks to it (on Linux
+    /// `lib<name>.so.<version>`, `lib<name>.so.<major>` and `lib<name>.so`)
+    pub fn dynamic_lib_names(&self, name: &str, version: &str) -> Vec<String> {
+        match *self {
+            ToolFamily::Msvc { .. } => vec![format!("{}.dll", name)],
+            _ if cfg!(target_os = "windows") => vec![format!("{}.dll", name)],
+            _ if cfg!(target_os = "macos") => {
+                vec![format!("lib{}.dylib", name)]
+            },
+            _ => {
+                let major = version.split('.').next().unwrap_or(version);
+                vec![
+                    format!("lib{}.so.{}", name, version),
+                    format!("lib{}.so.{}", name, major),
+                    format!("lib{}.so", name),
+                ]
+            },
+        }
+    }
+
+    /// Get the file name of the import library of a dll of `name` (only on
+    /// Windows)
+    pub fn import_lib_name(&self, name: &str) -> Option<String> {
+        match *self {
+            ToolFamily::Msvc { .. } => Some(format!("{}.lib", name)),
+            _ if cfg!(target_os = "windows") => {
+                Some(format!("lib{}.dll.a", name))
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the linker flag to find the dynamic libraries of `dir` at runtime
+    /// (Windows looks for them next to the executable)
+    pub fn rpath_flag(&self, dir: &Path) -> Option<OsString> {
+        match *self {
+            ToolFamily::Msvc { .. } => None,
+            _ if cfg!(target_os = "windows") => None,
+            _ => {
+                let mut flag = OsString::from("-Wl,-rpath,");
+                flag.push(dir);
+                Some(flag)
+            },
+        }
+    }
+
+    /// Get the flag to generate position independent code (if needed)
+    pub fn pic_flag(&self) -> Option<&'static str> {
+        match *self {
+            ToolFamily::Msvc { .. } => None,
+            _ if cfg!(target_os = "windows") => None,
+            _ => Some("-fPIC"),
+        }
+    }
+
+    /// Get the flag to generate a dynamic library
+    pub fn shared_flag(&self) -> &'static str {
+        match *self {
+            ToolFamily::Msvc { .. } => "/LD",
+            _ if cfg!(target_os = "macos") => "-dynamiclib",
+            _ => "-shared",
+        }
+    }
+
+    /// Get the linker flag to set the soname (install name on macOS) of a
+    /// dynamic library
+    pub fn soname_flag(&self, soname: &str) -> Option<OsString> {
+        match *self {
+            ToolFamily::Msvc { .. } => None,
+            _ if cfg!(target_os = "windows") => None,
+            _ if cfg!(target_os = "macos") => {
+                Some(format!("-Wl,-install_name,@rpath/{}", soname).into())
+            },
+            _ => Some(format!("-Wl,-soname,{}", soname).into()),
+        }
+    }
+
+    /// Get the linker flag to generate the import library of a dll
+    pub fn import_lib_flag(&self, path: &Path) -> OsString {
+        let mut flag = match *self {
+            ToolFamily::Msvc { .. } => OsString::from("/IMPLIB:"),
+            _ => OsString::from("-Wl,--out-implib,"),
+        };
+        flag.push(path);
+        flag
+    }
+
     /// Get the flags to generate a executable
     pub fn exe_flag(&self) -> &'static str {
         match *self {
@@ -214,6 +580,7 @@ impl ToolFamily {
     }
 
     /// What the flags to enable extra warnings
+    #[allow(unused)]
     pub fn extra_warnings_flags(&self) -> Option<&'static str> {
         match *self {
             ToolFamily::Msvc { .. } => None,
@@ -0,0 +1,79 @@
+/*
+ * amargo_bench.h: the benchmark harness of `amargo bench`
+ *
+ * Declare the benchmarks of a source of `benches/` with `BENCH(name)`, amargo
+ * finds them and generates the `main` that runs them, the body is run many
+ * times after a warmup and the time per iteration of every sample is printed
+ * for amargo to compute the statistics
+ *
+ *     #include "amargo_bench.h"
+ *
+ *     BENCH(add) {
+ *         BLACK_BOX(add(2, 2));
+ *     }
+ */
+#ifndef AMARGO_BENCH_H
+#define AMARGO_BENCH_H
+
+#include <stdio.h>
+
+#ifdef _WIN32
+#include <windows.h>
+#else
+#include <time.h>
+#endif
+
+/* Declare the benchmark `name` */
+#define BENCH(name) static void amargo_bench_##name(void)
+
+/* Use an integer or floating value so the compiler doesn't optimize away the
+ * code that computes it */
+static volatile double amargo_bench_sink;
+#define BLACK_BOX(value) (amargo_bench_sink = (double)(value))
+
+/* Monotonic time in seconds (if available) */
+static double amargo_bench_now(void) {
+#ifdef _WIN32
+    LARGE_INTEGER counter, frequency;
+    QueryPerformanceCounter(&counter);
+    QueryPerformanceFrequency(&frequency);
+    return (double)counter.QuadPart / (double)frequency.QuadPart;
+#elif defined(CLOCK_MONOTONIC)
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return (double)ts.tv_sec + (double)ts.tv_nsec / 1e9;
+#else
+    /* Strict ISO modes hide `clock_gettime`, fall back to processor time */
+    return (double)clock() / CLOCKS_PER_SEC;
+#endif
+}
+
+/* Warm up `bench` finding how many iterations take at least 10ms, then
+ * print those iterations and the nanoseconds per iteration of every sample */
+static void amargo_bench_run(void (*bench)(void), int samples) {
+    unsigned long iterations = 1, i;
+    double start = amargo_bench_now(), begin, elapsed;
+    int sample;
+
+    for (;;) {
+        begin = amargo_bench_now();
+        for (i = 0; i < iterations; i++)
+            bench();
+        elapsed = amargo_bench_now() - begin;
+        if (elapsed < 0.01)
+            iterations *= 2;
+        else if (amargo_bench_now() - start >= 0.1)
+            break;
+    }
+
+    printf("iterations %lu\n", iterations);
+    for (sample = 0; sample < samples; sample++) {
+        begin = amargo_bench_now();
+        for (i = 0; i < iterations; i++)
+            bench();
+        elapsed = amargo_bench_now() - begin;
+        printf("%f\n", elapsed * 1e9 / (double)iterations);
+    }
+}
+
+#endif /* AMARGO_BENCH_H */
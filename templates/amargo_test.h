@@ -0,0 +1,60 @@
+/*
+ * amargo_test.h: the test harness of `amargo test`
+ *
+ * Declare the test cases of a source of `tests/` with `TEST(name)`, amargo
+ * finds them and generates the `main` that runs them, every case runs on its
+ * own process and fails if any assertion fails or if it crashes
+ *
+ *     #include "amargo_test.h"
+ *
+ *     TEST(adds_two_numbers) {
+ *         ASSERT_EQ(add(2, 2), 4);
+ *     }
+ */
+#ifndef AMARGO_TEST_H
+#define AMARGO_TEST_H
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* Declare the test case `name` */
+#define TEST(name) static void amargo_test_##name(void)
+
+/* Fail the running test case with a printf like message */
+#define FAIL(...)                                                             \
+    do {                                                                      \
+        fprintf(stderr, "%s:%d: ", __FILE__, __LINE__);                       \
+        fprintf(stderr, __VA_ARGS__);                                         \
+        fputc('\n', stderr);                                                  \
+        exit(1);                                                              \
+    } while (0)
+
+#define ASSERT(cond)                                                          \
+    do {                                                                      \
+        if (!(cond))                                                          \
+            FAIL("assertion failed: %s", #cond);                              \
+    } while (0)
+
+#define ASSERT_EQ(left, right)                                                \
+    do {                                                                      \
+        if (!((left) == (right)))                                             \
+            FAIL("assertion failed: %s == %s", #left, #right);                \
+    } while (0)
+
+#define ASSERT_NE(left, right)                                                \
+    do {                                                                      \
+        if (!((left) != (right)))                                             \
+            FAIL("assertion failed: %s != %s", #left, #right);                \
+    } while (0)
+
+#define ASSERT_STR_EQ(left, right)                                            \
+    do {                                                                      \
+        const char *amargo_left = (left);                                     \
+        const char *amargo_right = (right);                                   \
+        if (strcmp(amargo_left, amargo_right) != 0)                           \
+            FAIL("assertion failed: %s == %s (\"%s\" != \"%s\")", #left,      \
+                 #right, amargo_left, amargo_right);                          \
+    } while (0)
+
+#endif /* AMARGO_TEST_H */
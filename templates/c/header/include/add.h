@@ -0,0 +1,8 @@
+#ifndef ADD
+#define ADD
+
+static inline int add(int a, int b) {
+    return a + b;
+}
+
+#endif // ADD
@@ -0,0 +1,10 @@
+#ifndef LIB
+#define LIB
+
+#include "add.h"
+
+static inline int add_twice(int a, int b) {
+    return add(add(a, b), b);
+}
+
+#endif // LIB
@@ -0,0 +1,9 @@
+#ifndef ADD
+#define ADD
+
+template <typename T>
+T add(T a, T b) {
+    return a + b;
+}
+
+#endif // ADD
@@ -0,0 +1,11 @@
+#ifndef LIB
+#define LIB
+
+#include "add.hpp"
+
+template <typename T>
+T add_twice(T a, T b) {
+    return add(add(a, b), b);
+}
+
+#endif // LIB
@@ -20,11 +20,82 @@ cargo build
 set +e
 
 # Tests for C
-$BIN new tests/c_binary
+(cd tests && ../$BIN new c_binary)
 (cd tests/c_binary && ../../$BIN build)
 echo "-------------------------------------------------------------------------"
-$BIN new tests/c_dylib -- dynamic
+(cd tests && ../$BIN new c_staticlib static)
+(cd tests/c_staticlib && ../../$BIN build)
+# Editing a header compiles again the sources that include it
+echo "// edited" >> tests/c_staticlib/include/lib.h
+(cd tests/c_staticlib && ../../$BIN build --message-format json) \
+    | grep compiler-started > /dev/null \
+    || echo "error: editing a header didn't compile c_staticlib again"
+mkdir -p tests/c_staticlib/tests
+cat > tests/c_staticlib/tests/add.c << EOF
+#include "amargo_test.h"
+#include "lib.h"
+
+TEST(adds) {
+    ASSERT_EQ(add(2, 2), 4);
+}
+EOF
+(cd tests/c_staticlib && ../../$BIN test)
+mkdir -p tests/c_staticlib/benches
+cat > tests/c_staticlib/benches/add.c << EOF
+#include "amargo_bench.h"
+#include "lib.h"
+
+BENCH(adds) {
+    BLACK_BOX(add(2, 2));
+}
+EOF
+(cd tests/c_staticlib && ../../$BIN bench && ../../$BIN bench)
+echo "-------------------------------------------------------------------------"
+(cd tests && ../$BIN new c_dylib dynamic)
 (cd tests/c_dylib && ../../$BIN build)
+(cd tests && ../$BIN new c_app_with_deps)
+cat >> tests/c_app_with_deps/Amargo.toml << EOF
+
+[dependencies]
+c_staticlib = { path = "../c_staticlib" }
+EOF
+cat > tests/c_app_with_deps/src/main.c << EOF
+#include <stdio.h>
+#include "lib.h"
+
+int main(void) {
+    printf("%d\\n", add(2, 2));
+    return 0;
+}
+EOF
+(cd tests/c_app_with_deps && ../../$BIN run)
 echo "-------------------------------------------------------------------------"
+(cd tests && ../$BIN new c_header header)
+(cd tests/c_header && ../../$BIN build)
+(cd tests && ../$BIN new c_header_with_deps header)
+cat >> tests/c_header_with_deps/Amargo.toml << EOF
 
-# Tests for C++ (not currently available)
+[dependencies]
+c_staticlib = { path = "../c_staticlib" }
+EOF
+rm tests/c_header_with_deps/include/lib.h
+cat > tests/c_header_with_deps/include/twice.h << EOF
+#include "lib.h"
+
+static inline int twice(int a) {
+    return add(a, a);
+}
+EOF
+(cd tests/c_header_with_deps && ../../$BIN build)
+# The includes of the headers of the dependencies are not inlined
+grep '#include "lib.h"' tests/c_header_with_deps/target/debug/*.h > /dev/null \
+    || echo "error: c_header_with_deps inlined the headers of its dependencies"
+echo "-------------------------------------------------------------------------"
+
+# Tests for C++
+(cd tests && ../$BIN new cpp_binary --lang cpp)
+(cd tests/cpp_binary && ../../$BIN build)
+echo "-------------------------------------------------------------------------"
+(cd tests && ../$BIN new cpp_staticlib static --lang cpp)
+(cd tests/cpp_staticlib && ../../$BIN build)
+echo "-------------------------------------------------------------------------"